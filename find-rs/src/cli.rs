@@ -1,33 +1,44 @@
 use std::{
     fmt,
-    fs::canonicalize,
-    env::current_dir,
+    fs::{self, canonicalize},
+    env::{self, current_dir},
     path::PathBuf,
+    time::{Duration, SystemTime},
 };
 
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
 use clap::{Arg, App, ArgMatches, Values};
 
+use lazy_static::lazy_static;
+
+use regex::{Regex, Captures};
+
+use finder::{ContentSearch, DiskUsage, DupeFinder, Exclude, FileType, FinderConfig, FindOnly, Glob, OrderBy, Pattern, PermMask, SizeMode, TimeField, TypeDetection};
+use finder::users;
 
 use utils::pretty_fs_size;
 
+use crate::exec::{CommandTemplate, ExecMode};
+use crate::output::OutputFormat;
+use crate::template::Template;
+
 
 static DEFAULT_FUZZY_THRESHOLD: isize = -100;
 
 //
 static SIZE_REGEX_STR: &str = r"^[+]?(?P<digits>\d+)[^a-z|A-Z]*(?P<unit>[kmgtKMGT]?[i]?[bB]?)?$";
 
+// One or more '<number><unit>' pairs, like "3d" or "2h30m"
+static DURATION_REGEX_STR: &str = r"^(\s*\d+\s*[a-zA-Z]+)+\s*$";
+static DURATION_PART_REGEX_STR: &str = r"(?P<digits>\d+)\s*(?P<unit>[a-zA-Z]+)";
 
-pub enum FindOnly {
-    Files,
-    Directories,
-}
-
-pub enum OrderByProperty {
-    Size,
-    Filename,
-}
+// Absolute dates, with an optional time. Interpreted in the local timezone.
+static DATE_TIME_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"];
+static DATE_FORMAT: &str = "%Y-%m-%d";
 
 
+#[derive(Debug)]
 pub struct Error {
     message: String,
 }
@@ -53,118 +64,348 @@ impl fmt::Display for Error {
 }
 
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexAction {
+    Build,
+    Update,
+}
+
+
 pub struct FindArgs {
     pub root: PathBuf,
+    pub glob_matchers: Option<Vec<Glob>>,
+    pub full_path: bool,
     pub regex_matchers: Option<Vec<Regex>>,
+    pub ext_matchers: Option<Vec<String>>,
     pub fuzzy_matchers: Option<Vec<String>>,
     pub fuzzy_thresh: isize,
+    pub grep_matchers: Option<Vec<Regex>>,
+    pub grep_context: usize,
+    pub grep_max_size: Option<usize>,
     pub min_size: Option<usize>,
     pub max_size: Option<usize>,
+    pub newer: Option<SystemTime>,
+    pub older: Option<SystemTime>,
+    pub changed_within: Option<SystemTime>,
+    pub time_field: TimeField,
+    pub min_depth: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub no_user: bool,
+    pub no_group: bool,
+    pub perm: Option<PermMask>,
+    pub exec: Option<CommandTemplate>,
+    pub output_format: OutputFormat,
+    pub print0: bool,
+    pub format: Option<Template>,
+    pub dupes: bool,
+    pub dupes_hard_links: bool,
+    pub du: bool,
+    pub tree: bool,
+    pub tree_totals: bool,
+    pub pick: bool,
+    pub query: Option<String>,
+    pub watch: bool,
+    pub index_action: Option<IndexAction>,
+    pub index_file: PathBuf,
+    pub from_index: bool,
+    pub apparent_size: bool,
     pub worker_threads: usize,
     pub find_only: Option<FindOnly>,
-    pub order_by: Option<OrderByProperty>,
+    pub kinds: Option<Vec<FileType>>,
+    pub type_detection: TypeDetection,
+    pub order_by: Option<OrderBy>,
     pub desc_order: bool,
+    pub limit: Option<usize>,
+    pub use_ignore_files: bool,
+    pub follow_symlinks: bool,
+    pub excludes: Vec<Exclude>,
+    pub one_file_system: bool,
+    pub pseudo_fs: bool,
+    pub skip_network_fs: bool,
+    pub broken_links: bool,
     pub print_stats: bool,
     pub verbose: bool,
 }
 
-/*
-fn build_regexes<F>(values: Values, formatter: F) -> Vec<Regex>
-where F: Fn(&str) -> String
-{
+
+fn build_globs(values: Values, full_path: bool) -> Result<Vec<Glob>, Error> {
     values.into_iter()
-        .filter_map(|val| {
-            let formatted = formatter(val);
-            Regex::new(&formatted).ok()
+        .map(|val| match full_path {
+            true => Glob::path(val.trim()),
+            false => Glob::new(val.trim()),
         })
+        .map(|glob| glob.map_err(|err| Error::from(&err)))
         .collect()
 }
-*/
 
+// Excludes are globs or exact names, and prunes are regexes
+fn build_excludes(exclude_vals: Option<Values>, prune_vals: Option<Values>) -> Result<Vec<Exclude>, Error> {
+    let mut excludes: Vec<Exclude> = exclude_vals.into_iter()
+        .flatten()
+        .map(|val| Exclude::parse(val.trim()).map_err(|err| Error::from(&err)))
+        .collect::<Result<_, _>>()?;
 
-impl FindArgs {
-    pub fn from_arg_matches(matches: &ArgMatches) -> Result<FindArgs, Error> {
-        println!("patterns: {}", match matches.values_of("patterns") {
-            Some(vals) => {
-                let pats: Vec<&str> = vals.into_iter().collect();
-                pats.join(", ")
-            },
-            None => String::from("No patterns")
-        });
+    if let Some(vals) = prune_vals {
+        excludes.extend(build_regexes(vals, |val| val.trim().to_string())?.into_iter().map(Exclude::regex));
+    }
 
-        println!("regexes: {}", match matches.values_of("regex") {
-            Some(vals) => {
-                let pats: Vec<&str> = vals.into_iter().collect();
-                pats.join(", ")
-            },
-            None => String::from("No regexes")
-        });
+    Ok(excludes)
+}
 
-        println!("exts: {}", match matches.values_of("exts") {
-            Some(vals) => {
-                let pats: Vec<&str> = vals.into_iter().collect();
-                pats.join(", ")
-            },
-            None => String::from("No exts")
-        });
+fn build_regexes<F>(values: Values, formatter: F) -> Result<Vec<Regex>, Error>
+where F: Fn(&str) -> String
+{
+    values.into_iter()
+        .map(|val| Regex::new(&formatter(val)).map_err(|err| Error::from(&err)))
+        .collect()
+}
 
 
-        /*
-        let mut patterns = vec![""];
-        if matches.is_present("videos") {
-            patterns.append(&mut vec!["mp4", "mkv", "mpeg", ""]);
-        }
-        else if matches.is_present("images") {
-            patterns.append(&mut vec!["png", "gif", "jpg", "jpeg", "avif"]);
-        }
-        else {
+impl FindArgs {
+    pub fn from_arg_matches(matches: &ArgMatches) -> Result<FindArgs, Error> {
+        let full_path = matches.is_present("full-path");
 
-        }
-        */
-        /*
-        let pattern_values = matches.values_of("patterns");
-        let regex_values = matches.values_of("regex");
-        let ext_values = matches.values_of("exts");
+        let glob_patterns = match matches.values_of("patterns") {
+            Some(vals) => Some(build_globs(vals, full_path)?),
+            None => None,
+        };
 
-        let mut regexes: Option<Vec<Regex>> = None;
-        if let Some(patterns) = pattern_values {
-            regexes = Some(build_regexes(patterns, |val| val.trim().replace("*", ".*")));
-        }
-        else if let Some(regex_patterns) = regex_values {
-            regexes = Some(build_regexes(regex_patterns, |val| val.trim().to_string()));
+        let regex_patterns = match matches.values_of("regex") {
+            Some(vals) => Some(build_regexes(vals, |val| val.trim().to_string())?),
+            None => None,
+        };
 
-        }
-        else if let Some(ext_patterns) = ext_values {
-            regexes = Some(build_regexes(ext_patterns, |ext| {
-                format!(".*{}$", ext.trim().replace(".", ""))
-            }));
-        }
+        let ext_patterns = matches.values_of("exts").map(|vals| {
+            vals.into_iter()
+                .map(|val| val.trim().to_string())
+                .collect()
+        });
+
+        let grep_patterns = match matches.values_of("grep") {
+            Some(vals) => Some(build_regexes(vals, |val| val.to_string())?),
+            None => None,
+        };
 
         let fuzzy_patterns = matches.values_of("fuzzy").map(|vals| {
             vals.into_iter()
                 .map(|val| val.to_string())
                 .collect()
         });
-        */
+
+        let time_field = get_time_field(matches.value_of("time-field"));
 
         Ok(FindArgs {
             root: get_root_path(matches.value_of("root"))?,
-            // regex_matchers: regexes,
-            fuzzy_matchers: None,// fuzzy_patterns,
+            glob_matchers: glob_patterns,
+            full_path,
+            regex_matchers: regex_patterns,
+            ext_matchers: ext_patterns,
+            fuzzy_matchers: fuzzy_patterns,
             fuzzy_thresh: parse_fuzzy_thresh(matches.value_of("fuzzy-thresh"))?,
+            grep_matchers: grep_patterns,
+            grep_context: get_context_lines(matches.value_of("context"))?,
+            grep_max_size: try_parse_size(matches.value_of("grep-max-size"))?,
             min_size: try_parse_size(matches.value_of("min-size"))?,
             max_size:  try_parse_size(matches.value_of("max-size"))?,
+            newer: try_parse_time(matches.value_of("newer"), time_field)?,
+            older: try_parse_time(matches.value_of("older"), time_field)?,
+            changed_within: try_parse_time(matches.value_of("changed-within"), TimeField::Changed)?,
+            time_field,
+            min_depth: try_parse_depth(matches.value_of("min-depth"))?,
+            max_depth: try_parse_depth(matches.value_of("max-depth"))?,
+            uid: try_parse_owner(matches.value_of("user"), users::uid_for_user, "user")?,
+            gid: try_parse_owner(matches.value_of("group"), users::gid_for_group, "group")?,
+            no_user: matches.is_present("no-user"),
+            no_group: matches.is_present("no-group"),
+            perm: try_parse_perm(matches.value_of("perm"))?,
+            exec: get_exec_template(matches),
+            output_format: get_output_format(matches.value_of("output")),
+            print0: matches.is_present("print0"),
+            format: matches.value_of("format")
+                .map(|format_str| Template::parse(format_str).map_err(Error::from_string))
+                .transpose()?,
             worker_threads: get_thread_count(matches.value_of("workers"))?,
-            find_only: matches.is_present("exts")
-                .then(|| FindOnly::Files) // Infered if exts was passed in
+            kinds: get_kinds(matches.values_of("kind"))?,
+            type_detection: get_type_detection(matches.value_of("detect")),
+            dupes: matches.is_present("dupes"),
+            dupes_hard_links: matches.is_present("count-hard-links"),
+            du: matches.is_present("du"),
+            tree: matches.is_present("tree"),
+            tree_totals: matches.is_present("tree-totals"),
+            pick: matches.is_present("pick"),
+            query: matches.value_of("query").map(String::from),
+            watch: matches.is_present("watch"),
+            index_action: get_index_action(matches.value_of("index")),
+            index_file: matches.value_of("index-file")
+                .map(PathBuf::from)
+                .unwrap_or_else(default_index_file),
+            from_index: matches.is_present("from-index"),
+            apparent_size: matches.is_present("apparent-size"),
+            find_only: (matches.is_present("exts") || matches.is_present("dupes"))
+                .then_some(FindOnly::Files) // Infered if exts or dupes was passed in
                 .or_else(|| get_find_only_type(matches.value_of("type"))), // otherwise, try and match the input if it exists.
-            order_by: get_order_by_prop(matches.value_of("order-by")),
-            desc_order: matches.is_present("desc"),
+            // Fuzzy matches are best shown best first, unless asked for something else
+            order_by: get_order_by_prop(matches.value_of("order-by"))
+                .or_else(|| matches.is_present("fuzzy").then_some(OrderBy::Score)),
+            desc_order: matches.is_present("desc") || (matches.is_present("fuzzy") && !matches.is_present("order-by")),
+            limit: try_parse_limit(matches.value_of("limit"))?,
+            use_ignore_files: matches.is_present("ignore-files"),
+            follow_symlinks: matches.is_present("follow"),
+            excludes: build_excludes(matches.values_of("exclude"), matches.values_of("prune"))?,
+            one_file_system: matches.is_present("xdev"),
+            pseudo_fs: matches.is_present("pseudo-fs"),
+            skip_network_fs: matches.is_present("skip-network-fs"),
+            broken_links: matches.is_present("broken-links"),
             print_stats: matches.is_present("stats") || matches.is_present("verbose"),
             verbose: matches.is_present("verbose"),
         })
 
     }
+
+    pub fn patterns(&self) -> Vec<Pattern> {
+        let mut patterns = Vec::new();
+
+        if let Some(globs) = &self.glob_matchers {
+            patterns.extend(globs.iter().cloned().map(Pattern::glob));
+        }
+
+        if let Some(regexes) = &self.regex_matchers {
+            patterns.extend(regexes.iter().cloned().map(Pattern::Regex));
+        }
+
+        if let Some(exts) = &self.ext_matchers {
+            patterns.extend(exts.iter().map(Pattern::ext));
+        }
+
+        if let Some(fuzzy_patterns) = &self.fuzzy_matchers {
+            patterns.extend(fuzzy_patterns.iter().map(|fuzzy| Pattern::fuzzy(fuzzy, Some(self.fuzzy_thresh))));
+        }
+
+        if let Some(newer) = self.newer {
+            patterns.push(Pattern::newer(self.time_field, newer));
+        }
+
+        if let Some(older) = self.older {
+            patterns.push(Pattern::older(self.time_field, older));
+        }
+
+        if let Some(changed_within) = self.changed_within {
+            patterns.push(Pattern::newer(TimeField::Changed, changed_within));
+        }
+
+        if let Some(grep_patterns) = &self.grep_matchers {
+            patterns.extend(grep_patterns.iter().map(|regex| {
+                let mut search = ContentSearch::new(regex.clone())
+                    .with_context_lines(self.grep_context);
+
+                if let Some(max_size) = self.grep_max_size {
+                    search = search.with_max_file_size(max_size as u64);
+                }
+
+                Pattern::contains(search)
+            }));
+        }
+
+        patterns
+    }
+
+    pub fn dupe_finder(&self) -> DupeFinder {
+        let mut dupes = DupeFinder::new()
+            .with_pool_size(self.worker_threads)
+            .with_hard_links(self.dupes_hard_links);
+
+        if let Some(min_size) = self.min_size {
+            dupes = dupes.with_min_size(min_size as u64);
+        }
+
+        dupes
+    }
+
+    pub fn disk_usage(&self) -> DiskUsage {
+        let size_mode = match self.apparent_size {
+            true => SizeMode::Apparent,
+            false => SizeMode::Blocks,
+        };
+
+        let mut usage = DiskUsage::new().with_size_mode(size_mode);
+
+        if let Some(max_depth) = self.max_depth {
+            usage = usage.with_max_depth(max_depth);
+        }
+
+        usage
+    }
+
+    pub fn finder_config(&self) -> FinderConfig {
+        let mut config = FinderConfig::from(self.root.clone())
+            .with_pool_size(self.worker_threads)
+            .with_patterns(self.patterns())
+            .with_excludes(self.excludes.clone())
+            .with_ignore_files(self.use_ignore_files)
+            .with_follow_symlinks(self.follow_symlinks)
+            .with_broken_links(self.broken_links)
+            .with_one_file_system(self.one_file_system)
+            .with_pseudo_fs(self.pseudo_fs)
+            .with_skip_network_fs(self.skip_network_fs);
+
+        if let Some(kinds) = &self.kinds {
+            config = config.file_types(kinds.clone())
+                .with_type_detection(self.type_detection);
+        }
+
+        if let Some(find_only) = self.find_only {
+            config = config.with_find_only(find_only);
+        }
+
+        if let Some(min_size) = self.min_size {
+            config = config.with_min_size(min_size as u64);
+        }
+
+        if let Some(max_size) = self.max_size {
+            config = config.with_max_size(max_size as u64);
+        }
+
+        if let Some(min_depth) = self.min_depth {
+            config = config.with_min_depth(min_depth);
+        }
+
+        // With --du the whole tree still gets walked, --max-depth just limits what's reported
+        if let Some(max_depth) = self.max_depth.filter(|_| !self.du) {
+            config = config.with_max_depth(max_depth);
+        }
+
+        if let Some(uid) = self.uid {
+            config = config.with_uid(uid);
+        }
+
+        if let Some(gid) = self.gid {
+            config = config.with_gid(gid);
+        }
+
+        if self.no_user {
+            config = config.with_no_user();
+        }
+
+        if self.no_group {
+            config = config.with_no_group();
+        }
+
+        if let Some(perm) = self.perm {
+            config = config.with_perm(perm);
+        }
+
+        if let Some(order_by) = self.order_by {
+            config = config.with_order_by(order_by, self.desc_order);
+        }
+
+        if let Some(limit) = self.limit {
+            config = config.with_limit(limit);
+        }
+
+        config
+    }
 }
 
 impl fmt::Display for FindArgs {
@@ -181,14 +422,33 @@ impl fmt::Display for FindArgs {
             _ => () // No need to specify we're looking for everything
         }
 
+        if let Some(kinds) = &self.kinds {
+            let kind_strs: Vec<String> = kinds.iter().map(|kind| kind.to_string()).collect();
+
+            let detection = match self.type_detection {
+                TypeDetection::Extension => "by extension",
+                TypeDetection::Sniff => "by file contents",
+                TypeDetection::Either => "by extension or file contents",
+            };
+
+            write!(f, "Kinds of file ({}) - {}\n", detection, kind_strs.join(", "))?;
+        }
+
         match self.worker_threads {
             0 | 1 => write!(f, "Using only the main thread\n")?,
             _ => write!(f, "Using {} Worker threads\n", self.worker_threads)?,
         }
 
-        /*
         // log whichever method we're using to match
-        if let Some(match_patterns) = &self.regex_matchers {
+        if let Some(glob_patterns) = &self.glob_matchers {
+            let glob_strs: Vec<String> = glob_patterns.iter()
+                .map(|glob| glob.to_string())
+                .collect();
+
+            let target = if self.full_path {"full path"} else {"name"};
+            write!(f, "Glob patterns ({}) - {}\n", target, glob_strs.join(", "))?;
+        }
+        else if let Some(match_patterns) = &self.regex_matchers {
             let match_strs: Vec<String> = match_patterns.iter()
                 .map(|reg| reg.to_string())
                 .collect();
@@ -199,9 +459,10 @@ impl fmt::Display for FindArgs {
                 _ => write!(f, "Regex patterns - {}\n", match_strs.join(", "))?,
             }
         }
-        else
-        */
-        if let Some(fuzzy_patterns) = &self.fuzzy_matchers {
+        else if let Some(ext_patterns) = &self.ext_matchers {
+            write!(f, "Extensions - {}\n", ext_patterns.join(", "))?;
+        }
+        else if let Some(fuzzy_patterns) = &self.fuzzy_matchers {
             write!(f, "Fuzzy matching with a score threshold of {}\n", self.fuzzy_thresh)?;
 
             match fuzzy_patterns.len() {
@@ -211,7 +472,19 @@ impl fmt::Display for FindArgs {
             }
         }
         else {
-            write!(f, "Matching all files")?;
+            write!(f, "Matching all files\n")?;
+        }
+
+        if let Some(grep_patterns) = &self.grep_matchers {
+            let grep_strs: Vec<String> = grep_patterns.iter()
+                .map(|reg| reg.to_string())
+                .collect();
+
+            write!(f, "Searching file contents for - {}\n", grep_strs.join(", "))?;
+
+            if self.grep_context > 0 {
+                write!(f, "Showing {} lines of context\n", self.grep_context)?;
+            }
         }
 
         // Print min/max sizes if specified
@@ -223,14 +496,149 @@ impl fmt::Display for FindArgs {
             write!(f, "Max file size - {}\n", pretty_fs_size(max_size))?;
         }
 
+        let time_field_name = time_field_name(self.time_field);
+
+        if let Some(newer) = self.newer {
+            write!(f, "Last {} after {}\n", time_field_name, pretty_time(newer))?;
+        }
+
+        if let Some(older) = self.older {
+            write!(f, "Last {} before {}\n", time_field_name, pretty_time(older))?;
+        }
+
+        if let Some(changed_within) = self.changed_within {
+            write!(f, "Last changed after {}\n", pretty_time(changed_within))?;
+        }
+
+        match (self.min_depth, self.max_depth) {
+            (Some(min_depth), Some(max_depth)) => write!(f, "Depth between {} and {}\n", min_depth, max_depth)?,
+            (Some(min_depth), None) => write!(f, "Min depth - {}\n", min_depth)?,
+            (None, Some(max_depth)) => write!(f, "Max depth - {}\n", max_depth)?,
+            (None, None) => (),
+        }
+
+        if let Some(uid) = self.uid {
+            write!(f, "Owned by uid {}\n", uid)?;
+        }
+
+        if let Some(gid) = self.gid {
+            write!(f, "Owned by gid {}\n", gid)?;
+        }
+
+        if self.no_user {
+            write!(f, "Owned by a user not in /etc/passwd\n")?;
+        }
+
+        if self.no_group {
+            write!(f, "Owned by a group not in /etc/group\n")?;
+        }
+
+        match self.perm {
+            Some(PermMask::Exact(bits)) => write!(f, "Permissions exactly {:04o}\n", bits)?,
+            Some(PermMask::Any(bits)) => write!(f, "Permissions with any of {:04o}\n", bits)?,
+            Some(PermMask::All(bits)) => write!(f, "Permissions with all of {:04o}\n", bits)?,
+            None => (),
+        }
+
+        if let Some(exec) = &self.exec {
+            match exec.mode {
+                ExecMode::Each => write!(f, "Running '{}' for each match\n", exec.args.join(" "))?,
+                ExecMode::Batch => write!(f, "Running '{}' on batches of matches\n", exec.args.join(" "))?,
+            }
+        }
+
+        match self.output_format {
+            OutputFormat::Plain if self.print0 => write!(f, "Separating results with NUL\n")?,
+            OutputFormat::Plain => (),
+            OutputFormat::Json => write!(f, "Printing results as a JSON array\n")?,
+            OutputFormat::JsonLines => write!(f, "Printing results as JSON lines\n")?,
+            OutputFormat::Csv => write!(f, "Printing results as CSV\n")?,
+            OutputFormat::Null => write!(f, "Not printing results\n")?,
+        }
+
+        if self.dupes {
+            match self.dupes_hard_links {
+                true => write!(f, "Finding duplicate files, counting hard links as duplicates\n")?,
+                false => write!(f, "Finding duplicate files\n")?,
+            }
+        }
+
+        if self.du {
+            match self.apparent_size {
+                true => write!(f, "Totalling apparent sizes of directories\n")?,
+                false => write!(f, "Totalling disk usage of directories\n")?,
+            }
+        }
+
+        if self.tree {
+            match self.tree_totals {
+                true => write!(f, "Printing results as a tree, with sizes and file counts\n")?,
+                false => write!(f, "Printing results as a tree\n")?,
+            }
+        }
+
+        if self.pick {
+            match &self.query {
+                Some(query) => write!(f, "Picking from the results, starting with the query '{}'\n", query)?,
+                None => write!(f, "Picking from the results\n")?,
+            }
+        }
+
+        match self.index_action {
+            Some(IndexAction::Build) => write!(f, "Building the index at {}\n", self.index_file.display())?,
+            Some(IndexAction::Update) => write!(f, "Updating the index at {}\n", self.index_file.display())?,
+            None if self.from_index => write!(f, "Searching the index at {}\n", self.index_file.display())?,
+            None => (),
+        }
+
+        if self.watch {
+            write!(f, "Watching for files that start or stop matching\n")?;
+        }
+
+        if let Some(format) = &self.format {
+            write!(f, "Printing results as '{}'\n", format)?;
+        }
+
+        if self.use_ignore_files {
+            write!(f, "Skipping anything matched by ignore files\n")?;
+        }
+
+        if self.follow_symlinks {
+            write!(f, "Following symlinks\n")?;
+        }
+
+        match self.excludes.len() {
+            0 => (),
+            1 => write!(f, "Excluding - {}\n", self.excludes[0])?,
+            _ => write!(f, "Excluding - {}\n", self.excludes.iter().map(Exclude::to_string).collect::<Vec<_>>().join(", "))?,
+        }
+
+        if self.one_file_system {
+            write!(f, "Staying on the root's filesystem\n")?;
+        }
+
+        if self.pseudo_fs {
+            write!(f, "Searching pseudo filesystems\n")?;
+        }
+
+        if self.skip_network_fs {
+            write!(f, "Skipping network filesystems\n")?;
+        }
+
         if let Some(order_by) = &self.order_by {
             let direction = if self.desc_order {"decending"} else {"ascending"};
             match order_by {
-                OrderByProperty::Size => write!(f, "Ordering by {} file size", direction)?,
-                OrderByProperty::Filename => write!(f, "Ordering by {} filename", direction)?,
+                OrderBy::Size => write!(f, "Ordering by {} file size", direction)?,
+                OrderBy::Filename => write!(f, "Ordering by {} filename", direction)?,
+                OrderBy::Score => write!(f, "Ordering by {} fuzzy score", direction)?,
             }
         }
 
+        if let Some(limit) = self.limit {
+            let sep = if self.order_by.is_some() {"\n"} else {""};
+            write!(f, "{}Showing at most {} results", sep, limit)?;
+        }
+
         write!(f, "\n")
     }
 }
@@ -265,6 +673,67 @@ fn get_thread_count(thread_arg: Option<&str>) -> Result<usize, Error> {
     Ok(num_cpus::get() - 1)
 }
 
+fn get_context_lines(context_arg: Option<&str>) -> Result<usize, Error> {
+    context_arg.map(|context_str| usize::from_str_radix(context_str.trim(), 10))
+        .unwrap_or(Ok(0))
+        .map_err(|err| Error::from(&err))
+}
+
+fn try_parse_depth(depth_arg: Option<&str>) -> Result<Option<usize>, Error> {
+    depth_arg.map(|depth_str| usize::from_str_radix(depth_str.trim(), 10))
+        .transpose()
+        .map_err(|err| Error::from(&err))
+}
+
+fn try_parse_limit(limit_arg: Option<&str>) -> Result<Option<usize>, Error> {
+    limit_arg.map(|limit_str| limit_str.trim().parse::<usize>())
+        .transpose()
+        .map_err(|err| Error::from(&err))
+}
+
+fn try_parse_owner<F>(owner_arg: Option<&str>, lookup: F, kind: &str) -> Result<Option<u32>, Error>
+where
+    F: Fn(&str) -> Option<u32>
+{
+    match owner_arg {
+        Some(owner_str) => lookup(owner_str)
+            .map(Some)
+            .ok_or_else(|| Error::from_string(format!("Unknown {} '{}'", kind, owner_str))),
+        None => Ok(None),
+    }
+}
+
+fn try_parse_perm(perm_arg: Option<&str>) -> Result<Option<PermMask>, Error> {
+    match perm_arg {
+        Some(perm_str) => PermMask::parse(perm_str)
+            .map(Some)
+            .ok_or_else(|| Error::from_string(format!("Invalid permission mask '{}'", perm_str))),
+        None => Ok(None),
+    }
+}
+
+fn get_exec_template(matches: &ArgMatches) -> Option<CommandTemplate> {
+    let (values, mode) = match (matches.values_of("exec"), matches.values_of("exec-batch")) {
+        (Some(values), _) => (values, ExecMode::Each),
+        (None, Some(values)) => (values, ExecMode::Batch),
+        (None, None) => return None,
+    };
+
+    CommandTemplate::new(values.map(String::from).collect(), mode)
+}
+
+fn get_output_format(output_arg: Option<&str>) -> OutputFormat {
+    let output_str = output_arg.unwrap_or("").trim().to_lowercase();
+
+    match output_str.as_str() {
+        "json" => OutputFormat::Json,
+        "jsonl" | "ndjson" => OutputFormat::JsonLines,
+        "csv" => OutputFormat::Csv,
+        "null" | "none" => OutputFormat::Null,
+        _ => OutputFormat::Plain,
+    }
+}
+
 fn try_parse_size(size_arg: Option<&str>) -> Result<Option<usize>, Error> {
     let size_str = match size_arg {
         Some(size_str) => size_str.trim(),
@@ -300,6 +769,128 @@ fn try_parse_size(size_arg: Option<&str>) -> Result<Option<usize>, Error> {
     Ok(Some(digits))
 }
 
+fn pretty_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Parses a point in time, which can be a duration before now ("3d", "2h30m"), a date in the
+/// local timezone ("2026-01-01", "2026-01-01 12:30"), or the path to a file to take the last
+/// modified time from.
+// A reference file is compared by the same time field as the matches, like `find -newerXY`
+fn try_parse_time(time_arg: Option<&str>, time_field: TimeField) -> Result<Option<SystemTime>, Error> {
+    let time_str = match time_arg {
+        Some(time_str) => time_str.trim(),
+        None => return Ok(None),
+    };
+
+    if let Some(duration) = try_parse_duration(time_str)? {
+        return SystemTime::now()
+            .checked_sub(duration)
+            .map(Some)
+            .ok_or_else(|| Error::from_string(format!("Duration {} is too far in the past", time_str)));
+    }
+
+    if let Some(date_time) = try_parse_date(time_str) {
+        return Ok(Some(date_time));
+    }
+
+    let meta = fs::metadata(time_str).map_err(|_| Error::from_string(format!(
+        "Could not parse '{}' as a duration, date, or reference file", time_str
+    )))?;
+
+    match time_field.of(&meta) {
+        Some(time) => Ok(Some(time)),
+        None => Err(Error::from_string(format!(
+            "Could not read the {} time of reference file '{}'", time_field_name(time_field), time_str
+        ))),
+    }
+}
+
+fn try_parse_duration(duration_str: &str) -> Result<Option<Duration>, Error> {
+    lazy_static! {
+        static ref DURATION_REGEX: Regex = match Regex::new(DURATION_REGEX_STR) {
+            Ok(regex) => regex,
+            Err(err) => panic!("Regex compile error: {}", err),
+        };
+
+        static ref DURATION_PART_REGEX: Regex = match Regex::new(DURATION_PART_REGEX_STR) {
+            Ok(regex) => regex,
+            Err(err) => panic!("Regex compile error: {}", err),
+        };
+    }
+
+    if !DURATION_REGEX.is_match(duration_str) {
+        return Ok(None);
+    }
+
+    let mut total_secs: u64 = 0;
+
+    for captures in DURATION_PART_REGEX.captures_iter(duration_str) {
+        let digits = match captures.name("digits").map(|mat| mat.as_str()) {
+            Some(digits_str) => u64::from_str_radix(digits_str, 10).map_err(|err| Error::from(&err))?,
+            _ => return Err(Error::from_string(format!("Could not extract digits in duration {}", duration_str))),
+        };
+
+        let unit = captures.name("unit").map(|mat| mat.as_str()).unwrap_or("");
+
+        let mult = get_duration_multiplier_from_units(unit)
+            .ok_or_else(|| Error::from_string(format!("Unknown time unit '{}' in {}", unit, duration_str)))?;
+
+        total_secs = digits.checked_mul(mult)
+            .and_then(|secs| total_secs.checked_add(secs))
+            .ok_or_else(|| Error::from_string(format!("Duration {} is too long", duration_str)))?;
+    }
+
+    Ok(Some(Duration::from_secs(total_secs)))
+}
+
+// Seconds per unit
+fn get_duration_multiplier_from_units(units: &str) -> Option<u64> {
+    match units.to_lowercase().as_str() {
+        "s" | "sec" | "secs" | "second" | "seconds" => Some(1),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(60),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Some(60 * 60),
+        "d" | "day" | "days" => Some(24 * 60 * 60),
+        "w" | "wk" | "wks" | "week" | "weeks" => Some(7 * 24 * 60 * 60),
+        "mo" | "month" | "months" => Some(30 * 24 * 60 * 60),
+        "y" | "yr" | "yrs" | "year" | "years" => Some(365 * 24 * 60 * 60),
+        _ => None,
+    }
+}
+
+fn try_parse_date(date_str: &str) -> Option<SystemTime> {
+    let naive = DATE_TIME_FORMATS.iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date_str, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(date_str, DATE_FORMAT).ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+
+    Local.from_local_datetime(&naive)
+        .earliest()
+        .map(SystemTime::from)
+}
+
+fn time_field_name(time_field: TimeField) -> &'static str {
+    match time_field {
+        TimeField::Modified => "modified",
+        TimeField::Accessed => "accessed",
+        TimeField::Changed => "changed",
+        TimeField::Created => "created",
+    }
+}
+
+fn get_time_field(field_arg: Option<&str>) -> TimeField {
+    let field_str = field_arg.unwrap_or("").trim().to_lowercase();
+
+    match field_str.as_str() {
+        "a" | "atime" | "accessed" => TimeField::Accessed,
+        "c" | "ctime" | "changed" => TimeField::Changed,
+        "b" | "btime" | "birth" | "created" => TimeField::Created,
+        _ => TimeField::Modified,
+    }
+}
+
 fn get_size_multiplier_from_units(units: &str) -> usize {
     let mut mult: usize = match units.to_lowercase().chars().nth(0) {
         Some('k') => 1024,
@@ -317,6 +908,47 @@ fn get_size_multiplier_from_units(units: &str) -> usize {
     mult
 }
 
+fn get_kinds(kind_args: Option<Values>) -> Result<Option<Vec<FileType>>, Error> {
+    kind_args.map(|kind_strs| {
+        kind_strs.into_iter()
+            .map(|kind_str| {
+                FileType::from_name(kind_str)
+                    .ok_or_else(|| Error::from_string(format!("Unknown kind of file '{}'", kind_str)))
+            })
+            .collect()
+    })
+    .transpose()
+}
+
+fn get_type_detection(detect_arg: Option<&str>) -> TypeDetection {
+    let detect_str = detect_arg.unwrap_or("").trim().to_lowercase();
+
+    match detect_str.as_str() {
+        "sniff" | "magic" | "content" => TypeDetection::Sniff,
+        "either" | "both" => TypeDetection::Either,
+        _ => TypeDetection::Extension,
+    }
+}
+
+fn get_index_action(index_arg: Option<&str>) -> Option<IndexAction> {
+    match index_arg?.trim().to_lowercase().as_str() {
+        "build" => Some(IndexAction::Build),
+        "update" => Some(IndexAction::Update),
+        _ => None,
+    }
+}
+
+// Under the XDG cache dir, falling back to ~/.cache like everything else does
+fn default_index_file() -> PathBuf {
+    let cache_dir = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(env::temp_dir);
+
+    cache_dir.join("find-rs").join("index")
+}
+
 fn get_find_only_type(find_arg: Option<&str>) -> Option<FindOnly> {
     let find_only_str = find_arg.unwrap_or("").trim().to_lowercase();
 
@@ -327,12 +959,13 @@ fn get_find_only_type(find_arg: Option<&str>) -> Option<FindOnly> {
     }
 }
 
-fn get_order_by_prop(order_arg: Option<&str>) -> Option<OrderByProperty> {
+fn get_order_by_prop(order_arg: Option<&str>) -> Option<OrderBy> {
     let order_str = order_arg.unwrap_or("").trim().to_lowercase();
 
     match order_str.to_lowercase().as_str() {
-        "s" | "sz" | "size" => Some(OrderByProperty::Size),
-        "f" | "fn" | "filename" => Some(OrderByProperty::Filename),
+        "s" | "sz" | "size" => Some(OrderBy::Size),
+        "f" | "fn" | "filename" => Some(OrderBy::Filename),
+        "sc" | "score" => Some(OrderBy::Score),
         _ => None,
     }
 }
@@ -344,19 +977,30 @@ pub fn parse_cli() -> Result<FindArgs, Error> {
         .required(true);
 
     let pattern_arg = Arg::with_name("patterns")
-        .help("The pattern(s) to match file/directory names against.")
+        .help("Glob pattern(s) to match file/directory names against. Supports '*', '?', '[...]', '{a,b}' and '**'. Patterns with a '/' in them are matched against the full path")
         .multiple(true);
 
+    let full_path_arg = Arg::with_name("full-path")
+        .help("Match glob patterns against the full path, even if they don't have a '/' in them")
+        .short("p")
+        .long("full-path")
+        .required(false)
+        .takes_value(false);
+
     let exts_arg = Arg::with_name("exts")
         .help("File extensions to search for. Implies '--type file'")
         .long("exts")
         .short("e")
+        .takes_value(true)
+        .multiple(true)
         .conflicts_with_all(&["regex", "fuzzy"]);
 
     let regex_arg = Arg::with_name("regex")
         .help("Regex patten to match")
         .long("regex")
         .short("r")
+        .takes_value(true)
+        .multiple(true)
         .conflicts_with_all(&["exts", "fuzzy"]);
 
     let fuzzy_arg = Arg::with_name("fuzzy")
@@ -373,6 +1017,26 @@ pub fn parse_cli() -> Result<FindArgs, Error> {
         .required(false)
         .takes_value(true);
 
+    let grep_arg = Arg::with_name("grep")
+        .help("Regex to search for inside each file. Files that look binary are skipped")
+        .long("grep")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1);
+
+    let context_arg = Arg::with_name("context")
+        .help("Number of lines of context to print around each --grep match")
+        .short("C")
+        .long("context")
+        .takes_value(true)
+        .requires("grep");
+
+    let grep_max_size_arg = Arg::with_name("grep-max-size")
+        .help("Skip searching the contents of files bigger than this. Takes the same formats as --max-size")
+        .long("grep-max-size")
+        .takes_value(true)
+        .requires("grep");
+
     let min_size_arg = Arg::with_name("min-size")
         .help("Minimum file size threshold. Can be a number in bytes, or a human readable string (ex. '4MiB', '4k', '5MB', etc)")
         .long("min-size")
@@ -385,6 +1049,204 @@ pub fn parse_cli() -> Result<FindArgs, Error> {
         .takes_value(true)
         .required(false);
 
+    let newer_arg = Arg::with_name("newer")
+        .help("Only match entries newer than this. Can be a duration before now (ex. '3d', '2h30m'), a date ('2026-01-01', '2026-01-01 12:30'), or a file to compare against")
+        .long("newer")
+        .takes_value(true)
+        .required(false);
+
+    let older_arg = Arg::with_name("older")
+        .help("Only match entries older than this. Takes the same formats as --newer")
+        .long("older")
+        .takes_value(true)
+        .required(false);
+
+    let changed_within_arg = Arg::with_name("changed-within")
+        .help("Only match entries whose inode changed within this long (ex. '3d'). Takes the same formats as --newer")
+        .long("changed-within")
+        .takes_value(true)
+        .required(false);
+
+    let time_field_arg = Arg::with_name("time-field")
+        .help("Which timestamp --newer and --older compare against. Defaults to the modified time")
+        .long("time-field")
+        .takes_value(true)
+        .possible_values(&["m", "a", "c", "b", "mtime", "atime", "ctime", "btime", "modified", "accessed", "changed", "created", "birth"])
+        .required(false);
+
+    let min_depth_arg = Arg::with_name("min-depth")
+        .help("Only match entries at least this many directories below the root. Entries in the root are at depth 1")
+        .long("min-depth")
+        .takes_value(true)
+        .required(false);
+
+    let max_depth_arg = Arg::with_name("max-depth")
+        .help("Don't descend more than this many directories below the root")
+        .long("max-depth")
+        .takes_value(true)
+        .required(false);
+
+    let user_arg = Arg::with_name("user")
+        .help("Only match entries owned by this user. Can be a name or a uid")
+        .long("user")
+        .visible_alias("uid")
+        .takes_value(true)
+        .required(false);
+
+    let group_arg = Arg::with_name("group")
+        .help("Only match entries owned by this group. Can be a name or a gid")
+        .long("group")
+        .visible_alias("gid")
+        .takes_value(true)
+        .required(false);
+
+    let no_user_arg = Arg::with_name("no-user")
+        .help("Only match entries whose owner isn't in /etc/passwd")
+        .long("nouser")
+        .visible_alias("no-user")
+        .conflicts_with("user")
+        .required(false)
+        .takes_value(false);
+
+    let no_group_arg = Arg::with_name("no-group")
+        .help("Only match entries whose group isn't in /etc/group")
+        .long("nogroup")
+        .visible_alias("no-group")
+        .conflicts_with("group")
+        .required(false)
+        .takes_value(false);
+
+    let perm_arg = Arg::with_name("perm")
+        .help("Only match entries with these permission bits, in octal. '644' must match exactly, '-022' needs all of the bits set, and '/022' needs any of them")
+        .long("perm")
+        .takes_value(true)
+        .allow_hyphen_values(true)
+        .required(false);
+
+    let exec_arg = Arg::with_name("exec")
+        .help("Run a command for each match instead of printing it, with '{}' replaced by the path, '{/}' by its file name, and '{.}' and '{/.}' by those without the extension (or the path added to the end). Everything up to a ';' is part of the command, so escape it from your shell (ex. --exec echo {} \\;)")
+        .long("exec")
+        .takes_value(true)
+        .multiple(true)
+        .allow_hyphen_values(true)
+        .value_terminator(";")
+        .conflicts_with("exec-batch");
+
+    let exec_batch_arg = Arg::with_name("exec-batch")
+        .help("Run a command once with as many matches as fit on the command line, where a lone '{}', '{/}', '{.}' or '{/.}' is replaced by the paths (or they're added to the end). Everything up to a '+' is part of the command (ex. --exec-batch rm {} +)")
+        .long("exec-batch")
+        .takes_value(true)
+        .multiple(true)
+        .allow_hyphen_values(true)
+        .value_terminator("+");
+
+    let output_arg = Arg::with_name("output")
+        .help("How to print results. The structured formats include each match's size, mtime, mode, uid, inode and type")
+        .long("output")
+        .takes_value(true)
+        .possible_values(&["plain", "json", "jsonl", "ndjson", "csv", "null", "none"])
+        .conflicts_with_all(&["exec", "exec-batch"])
+        .required(false);
+
+    let print0_arg = Arg::with_name("print0")
+        .help("Separate results with a NUL instead of a newline, for 'xargs -0' and friends")
+        .short("0")
+        .long("print0")
+        .conflicts_with_all(&["output", "exec", "exec-batch"])
+        .required(false)
+        .takes_value(false);
+
+    let format_arg = Arg::with_name("format")
+        .help("Print each match using a template. Placeholders are {path}, {name}, {stem}, {ext}, {parent}, {size}, {size:h}, {mtime}, {mtime:<strftime format>}, {mode}, {depth} and {score}. Use {{ and }} for literal braces, and \\t, \\n or \\0 for tabs, newlines and NULs")
+        .long("format")
+        .takes_value(true)
+        .conflicts_with_all(&["output", "exec", "exec-batch"])
+        .required(false);
+
+    let dupes_arg = Arg::with_name("dupes")
+        .help("Print groups of files with identical contents, and how much space each group wastes. Use --min-size to skip small files")
+        .long("dupes")
+        .conflicts_with_all(&["exec", "exec-batch", "output", "format", "order-by", "print0", "limit"])
+        .required(false)
+        .takes_value(false);
+
+    let count_hard_links_arg = Arg::with_name("count-hard-links")
+        .help("With --dupes, report hard links to the same file as duplicates of each other, rather than as one file")
+        .long("count-hard-links")
+        .requires("dupes")
+        .required(false)
+        .takes_value(false);
+
+    let du_arg = Arg::with_name("du")
+        .help("Print the total size and inode count of each directory, counting hard links once. With --max-depth, only directories up to that depth are printed")
+        .long("du")
+        .conflicts_with_all(&["exec", "exec-batch", "output", "format", "order-by", "print0", "dupes", "limit"])
+        .required(false)
+        .takes_value(false);
+
+    let apparent_size_arg = Arg::with_name("apparent-size")
+        .help("With --du, total up file lengths instead of the disk space they take up")
+        .long("apparent-size")
+        .requires("du")
+        .required(false)
+        .takes_value(false);
+
+    let tree_arg = Arg::with_name("tree")
+        .help("Print matches as a tree of the directories that contain them")
+        .long("tree")
+        .conflicts_with_all(&["exec", "exec-batch", "output", "format", "order-by", "print0", "dupes", "du"])
+        .required(false)
+        .takes_value(false);
+
+    let tree_totals_arg = Arg::with_name("tree-totals")
+        .help("With --tree, show the size of each file, and the total size and number of matched files under each directory")
+        .long("tree-totals")
+        .requires("tree")
+        .required(false)
+        .takes_value(false);
+
+    let pick_arg = Arg::with_name("pick")
+        .help("Pick from the results in a full screen fuzzy finder, and print the chosen paths. Type to filter, tab to select more than one, enter to accept and esc to cancel")
+        .long("pick")
+        .conflicts_with_all(&["exec", "exec-batch", "output", "format", "order-by", "dupes", "du", "tree"])
+        .required(false)
+        .takes_value(false);
+
+    let query_arg = Arg::with_name("query")
+        .help("With --pick, the query to start with")
+        .long("query")
+        .requires("pick")
+        .takes_value(true)
+        .required(false);
+
+    let watch_arg = Arg::with_name("watch")
+        .help("Keep running after the search, and print files as they start ('+ path') or stop ('- path') matching. With --exec, the command is run on each new match instead. Linux only")
+        .long("watch")
+        .conflicts_with_all(&["output", "format", "order-by", "dupes", "du", "tree", "pick", "limit"])
+        .required(false)
+        .takes_value(false);
+
+    let index_arg = Arg::with_name("index")
+        .help("Build an index of everything under the root, or update an existing one by re-reading only the directories that changed. --exclude, --prune, -x, --skip-network-fs and --pseudo-fs decide what it covers, so pass the same ones to both. Use --from-index to search it")
+        .long("index")
+        .takes_value(true)
+        .possible_values(&["build", "update"])
+        .conflicts_with_all(&["from-index", "exec", "exec-batch", "output", "format", "dupes", "du", "tree", "pick", "watch", "limit"])
+        .required(false);
+
+    let index_file_arg = Arg::with_name("index-file")
+        .help("Where the index lives. Defaults to $XDG_CACHE_HOME/find-rs/index")
+        .long("index-file")
+        .takes_value(true)
+        .required(false);
+
+    let from_index_arg = Arg::with_name("from-index")
+        .help("Search the index instead of walking the tree. Nothing on disk is read, so results are as of the last --index update: deleted files still show up and new ones won't. Filters that need the files themselves (owners, permissions, contents, atime/ctime) aren't supported")
+        .long("from-index")
+        .conflicts_with_all(&["ignore-files", "follow", "format", "dupes", "du", "tree", "pick", "watch"])
+        .required(false)
+        .takes_value(false);
+
     let workers_arg = Arg::with_name("workers")
         .help("Maximum number of worker threads to use. Defaults to 'num_cpus - 1'")
         .short("w")
@@ -405,7 +1267,13 @@ pub fn parse_cli() -> Result<FindArgs, Error> {
         .short("o")
         .required(false)
         .takes_value(true)
-        .possible_values(&["s", "f", "sz", "fn", "size", "filename"]);
+        .possible_values(&["s", "f", "sz", "fn", "sc", "size", "filename", "score"]);
+
+    let limit_arg = Arg::with_name("limit")
+        .help("Stop after this many results. With --order-by (or --fuzzy, which orders by score) these are the best N, found without holding on to the rest")
+        .long("limit")
+        .takes_value(true)
+        .required(false);
 
     let desc_arg = Arg::with_name("desc")
         .help("Use decending order when --order-by is specified (defaults to ascending)")
@@ -414,6 +1282,62 @@ pub fn parse_cli() -> Result<FindArgs, Error> {
         .required(false)
         .takes_value(false);
 
+    let ignore_files_arg = Arg::with_name("ignore-files")
+        .help("Skip anything matched by .gitignore, .ignore, .findignore or .git/info/exclude files")
+        .long("ignore-files")
+        .short("i")
+        .required(false)
+        .takes_value(false);
+
+    let follow_arg = Arg::with_name("follow")
+        .help("Follow symlinks, searching inside linked directories. Links that loop back on themselves are skipped")
+        .short("L")
+        .long("follow")
+        .required(false)
+        .takes_value(false);
+
+    let broken_links_arg = Arg::with_name("broken-links")
+        .help("When following symlinks, report links to missing files on stderr")
+        .long("broken-links")
+        .requires("follow")
+        .required(false)
+        .takes_value(false);
+
+    let exclude_arg = Arg::with_name("exclude")
+        .help("Leave out anything matching this glob or exact name, without reading excluded directories at all. Matched against the name, or the full path if it has a '/' in it")
+        .long("exclude")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .required(false);
+
+    let prune_arg = Arg::with_name("prune")
+        .help("Like --exclude, but with a regex matched against the full path")
+        .long("prune")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .required(false);
+
+    let xdev_arg = Arg::with_name("xdev")
+        .help("Stay on the root's filesystem, without descending into anything mounted below it")
+        .short("x")
+        .long("xdev")
+        .required(false)
+        .takes_value(false);
+
+    let pseudo_fs_arg = Arg::with_name("pseudo-fs")
+        .help("Also search pseudo filesystems like /proc, /sys and /dev, which are skipped by default")
+        .long("pseudo-fs")
+        .required(false)
+        .takes_value(false);
+
+    let skip_network_fs_arg = Arg::with_name("skip-network-fs")
+        .help("Don't descend into network filesystems like nfs, cifs and sshfs")
+        .long("skip-network-fs")
+        .required(false)
+        .takes_value(false);
+
     let stats_arg = Arg::with_name("stats")
         .help("After searching, print some simple stats about the search performed.")
         .long("stats")
@@ -427,19 +1351,22 @@ pub fn parse_cli() -> Result<FindArgs, Error> {
         .required(false)
         .takes_value(false);
 
-    let images_arg = Arg::with_name("images")
-        .help("Shortcuts to search for image file types")
-        .long("images")
-        .visible_aliases(&["image", "img", "imgs"])
-        .required(false)
-        .takes_value(false);
+    let kind_arg = Arg::with_name("kind")
+        .help("Only match these kinds of file: image, video, audio, text, code, json, archive, document, spreadsheet, font, executable or config. Can also be a MIME type, like 'image/*' or 'application/pdf'")
+        .long("kind")
+        .short("k")
+        .takes_value(true)
+        .multiple(true)
+        .use_delimiter(true)
+        .required(false);
 
-    let videos_arg = Arg::with_name("videos")
-        .help("Shortcuts to search for video file types")
-        .long("videos")
-        .visible_aliases(&["video", "vid", "vids"])
-        .required(false)
-        .takes_value(false);
+    let detect_arg = Arg::with_name("detect")
+        .help("How to tell what kind of file something is for --kind. 'sniff' reads the start of each file, which catches files without (or with the wrong) extension. Defaults to 'ext'")
+        .long("detect")
+        .takes_value(true)
+        .possible_values(&["ext", "extension", "sniff", "magic", "content", "either", "both"])
+        .requires("kind")
+        .required(false);
 
     let matches = App::new("find-rs")
         .author("mrudisel")
@@ -447,21 +1374,118 @@ pub fn parse_cli() -> Result<FindArgs, Error> {
         .version("0.1")
         .arg(root_arg)
         .arg(pattern_arg)
+        .arg(full_path_arg)
         .arg(exts_arg)
         .arg(regex_arg)
         .arg(fuzzy_arg)
         .arg(fuzzy_thresh_arg)
+        .arg(grep_arg)
+        .arg(context_arg)
+        .arg(grep_max_size_arg)
         .arg(min_size_arg)
         .arg(max_size_arg)
+        .arg(newer_arg)
+        .arg(older_arg)
+        .arg(changed_within_arg)
+        .arg(time_field_arg)
+        .arg(min_depth_arg)
+        .arg(max_depth_arg)
+        .arg(user_arg)
+        .arg(group_arg)
+        .arg(no_user_arg)
+        .arg(no_group_arg)
+        .arg(perm_arg)
+        .arg(exec_arg)
+        .arg(exec_batch_arg)
+        .arg(output_arg)
+        .arg(print0_arg)
+        .arg(format_arg)
+        .arg(dupes_arg)
+        .arg(count_hard_links_arg)
+        .arg(du_arg)
+        .arg(apparent_size_arg)
+        .arg(tree_arg)
+        .arg(tree_totals_arg)
+        .arg(pick_arg)
+        .arg(query_arg)
+        .arg(watch_arg)
+        .arg(index_arg)
+        .arg(index_file_arg)
+        .arg(from_index_arg)
         .arg(workers_arg)
         .arg(type_arg)
         .arg(order_by_arg)
+        .arg(limit_arg)
         .arg(desc_arg)
-        .arg(images_arg)
-        .arg(videos_arg)
+        .arg(kind_arg)
+        .arg(detect_arg)
+        .arg(ignore_files_arg)
+        .arg(follow_arg)
+        .arg(broken_links_arg)
+        .arg(exclude_arg)
+        .arg(prune_arg)
+        .arg(xdev_arg)
+        .arg(pseudo_fs_arg)
+        .arg(skip_network_fs_arg)
         .arg(stats_arg)
         .arg(verbose_arg)
         .get_matches();
 
     FindArgs::from_arg_matches(&matches)
 }
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs::{File, FileTimes};
+    use std::{env, process};
+
+    #[test]
+    fn time_durations() {
+        let before = SystemTime::now();
+        let time = try_parse_time(Some("2h30m"), TimeField::Modified).unwrap().unwrap();
+        let after = SystemTime::now();
+
+        let two_and_a_half_hours = Duration::from_secs(150 * 60);
+        assert!(time >= before - two_and_a_half_hours && time <= after - two_and_a_half_hours);
+
+        assert_eq!(try_parse_duration("3d").unwrap(), Some(Duration::from_secs(3 * 24 * 60 * 60)));
+        assert_eq!(try_parse_duration("1w 2days").unwrap(), Some(Duration::from_secs(9 * 24 * 60 * 60)));
+        assert!(try_parse_duration("3 fortnights").is_err());
+
+        assert_eq!(try_parse_time(None, TimeField::Modified).unwrap(), None);
+    }
+
+    #[test]
+    fn time_dates() {
+        let expected = Local.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(try_parse_time(Some("2026-01-01"), TimeField::Modified).unwrap(), Some(SystemTime::from(expected)));
+
+        let expected = Local.with_ymd_and_hms(2026, 1, 1, 13, 45, 0).unwrap();
+        assert_eq!(try_parse_time(Some("2026-01-01T13:45"), TimeField::Modified).unwrap(), Some(SystemTime::from(expected)));
+
+        assert!(try_parse_time(Some("2026-13-01"), TimeField::Modified).is_err());
+    }
+
+    #[test]
+    fn time_reference_file() {
+        let path = env::temp_dir().join(format!("find-rs-test-{}-reference", process::id()));
+        let file = File::create(&path).unwrap();
+
+        let accessed = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        file.set_times(FileTimes::new().set_accessed(accessed).set_modified(modified)).unwrap();
+
+        let path_str = path.to_str().unwrap();
+        let by_mtime = try_parse_time(Some(path_str), TimeField::Modified);
+        let by_atime = try_parse_time(Some(path_str), TimeField::Accessed);
+
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(by_mtime.unwrap(), Some(modified));
+        assert_eq!(by_atime.unwrap(), Some(accessed));
+        assert!(try_parse_time(Some(path_str), TimeField::Modified).is_err());
+    }
+}
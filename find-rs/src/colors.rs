@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, Metadata};
+use std::io::Stdout;
+
+#[cfg(unix)]
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+#[cfg(unix)]
+use std::path::Path;
+
+use finder::FileInfo;
+#[cfg(unix)]
+use finder::EntryKind;
+
+
+// The same defaults as dircolors, for when LS_COLORS is missing or leaves a type out
+static DEFAULT_TYPE_COLORS: &[(&str, &str)] = &[
+    ("di", "01;34"),
+    ("ln", "01;36"),
+    ("or", "40;31;01"),
+    ("pi", "40;33"),
+    ("so", "01;35"),
+    ("bd", "40;33;01"),
+    ("cd", "40;33;01"),
+    ("su", "37;41"),
+    ("sg", "30;43"),
+    ("tw", "30;42"),
+    ("ow", "34;42"),
+    ("st", "37;44"),
+    ("ex", "01;32"),
+];
+
+// Only used without LS_COLORS, otherwise the user's extensions replace these entirely
+static DEFAULT_EXT_COLORS: &[(&str, &str)] = &[
+    ("tar", "01;31"), ("tgz", "01;31"), ("gz", "01;31"), ("xz", "01;31"), ("zst", "01;31"),
+    ("bz2", "01;31"), ("zip", "01;31"), ("7z", "01;31"), ("rar", "01;31"), ("deb", "01;31"),
+    ("rpm", "01;31"), ("jar", "01;31"),
+    ("jpg", "01;35"), ("jpeg", "01;35"), ("png", "01;35"), ("gif", "01;35"), ("bmp", "01;35"),
+    ("svg", "01;35"), ("webp", "01;35"), ("mp4", "01;35"), ("mkv", "01;35"), ("webm", "01;35"),
+    ("avi", "01;35"), ("mov", "01;35"),
+    ("mp3", "00;36"), ("flac", "00;36"), ("ogg", "00;36"), ("wav", "00;36"), ("m4a", "00;36"),
+    ("opus", "00;36"),
+];
+
+static RESET: &str = "\x1b[0m";
+
+
+/// Picks a colour for each path by its file type and name, the same way `ls --color` does.
+/// Colours are raw SGR codes (ex. "01;34"), and a type set to nothing (or "0") isn't coloured.
+#[derive(Debug, Clone, Default)]
+pub struct LsColors {
+    // Keyed by the two letter type codes, ex. "di" for directories
+    types: HashMap<String, String>,
+    // Lowercased extensions without the dot, for the common "*.ext" entries
+    exts: HashMap<String, String>,
+    // Any other "*suffix" entries, lowercased. These are usually more specific than a plain
+    // extension (ex. "*.tar.gz"), so they're checked first, with later entries winning as in ls.
+    suffixes: Vec<(String, String)>,
+}
+
+impl LsColors {
+    /// Reads LS_COLORS, falling back to the built in palette for anything it doesn't set.
+    pub fn from_env() -> Self {
+        match env::var("LS_COLORS") {
+            Ok(ls_colors) if !ls_colors.is_empty() => Self::parse(&ls_colors),
+            _ => Self::default_palette(),
+        }
+    }
+
+    /// Colours for stdout, or None if it isn't a terminal or the user asked for no colour.
+    pub fn for_stdout(stdout: &Stdout) -> Option<Self> {
+        let no_color = env::var_os("NO_COLOR").map(|val| !val.is_empty()).unwrap_or(false);
+
+        match !no_color && termion::is_tty(stdout) {
+            true => Some(Self::from_env()),
+            false => None,
+        }
+    }
+
+    pub fn default_palette() -> Self {
+        let mut colors = Self::with_default_types();
+
+        colors.exts = DEFAULT_EXT_COLORS.iter()
+            .map(|(ext, code)| (ext.to_string(), code.to_string()))
+            .collect();
+
+        colors
+    }
+
+    /// Parses the `key=codes:key=codes` format of LS_COLORS. Anything malformed is skipped.
+    pub fn parse(ls_colors: &str) -> Self {
+        let mut colors = Self::with_default_types();
+
+        for entry in ls_colors.split(':') {
+            let (key, code) = match entry.split_once('=') {
+                Some((key, code)) if !key.is_empty() => (key, code),
+                _ => continue,
+            };
+
+            match key.strip_prefix('*') {
+                Some(suffix) => match suffix.strip_prefix('.').filter(|ext| !ext.contains('.')) {
+                    Some(ext) => { colors.exts.insert(ext.to_lowercase(), code.to_string()); },
+                    None => colors.suffixes.push((suffix.to_lowercase(), code.to_string())),
+                },
+                None => { colors.types.insert(key.to_string(), code.to_string()); },
+            }
+        }
+
+        colors
+    }
+
+    fn with_default_types() -> Self {
+        Self {
+            types: DEFAULT_TYPE_COLORS.iter()
+                .map(|(key, code)| (key.to_string(), code.to_string()))
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    /// The colour for a match, or None if it should be left plain.
+    pub fn style(&self, file_info: &FileInfo) -> Option<&str> {
+        if file_info.broken_link {
+            return self.type_style("or").or_else(|| self.type_style("ln"));
+        }
+
+        let name = file_info.path.file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match classify(&file_info.meta) {
+            "fi" => self.name_style(&name).or_else(|| self.type_style("fi")),
+            // Links are only followed with --follow, so one that goes nowhere hasn't been flagged
+            "ln" if fs::metadata(&file_info.path).is_err() => self.type_style("or").or_else(|| self.type_style("ln")),
+            key => self.type_style(key),
+        }
+    }
+
+    /// The colour for a match found in the index, which only knows the kind of file. Special
+    /// permission bits and where links go aren't kept, so they're coloured as plain files
+    /// and links.
+    #[cfg(unix)]
+    pub fn kind_style(&self, path: &Path, kind: EntryKind) -> Option<&str> {
+        let name = path.file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match kind {
+            EntryKind::File => self.name_style(&name).or_else(|| self.type_style("fi")),
+            EntryKind::Dir => self.type_style("di"),
+            EntryKind::Symlink => self.type_style("ln"),
+            EntryKind::Other => None,
+        }
+    }
+
+    /// The colour for directories, used for the parent part of each path.
+    pub fn dir_style(&self) -> Option<&str> {
+        self.type_style("di")
+    }
+
+    fn type_style(&self, key: &str) -> Option<&str> {
+        self.types.get(key).map(String::as_str).filter(|code| is_visible(code))
+    }
+
+    fn name_style(&self, name: &str) -> Option<&str> {
+        let suffix_style = self.suffixes.iter()
+            .rev()
+            .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+            .map(|(_, code)| code.as_str());
+
+        suffix_style
+            .or_else(|| name.rsplit_once('.').and_then(|(_, ext)| self.exts.get(ext)).map(String::as_str))
+            .filter(|code| is_visible(code))
+    }
+}
+
+
+/// Wraps some text in a colour, if there is one.
+pub fn paint(text: &str, style: Option<&str>) -> String {
+    match style {
+        Some(code) => format!("\x1b[{}m{}{}", code, text, RESET),
+        None => text.to_string(),
+    }
+}
+
+fn is_visible(code: &str) -> bool {
+    !code.is_empty() && code.split(';').any(|part| !part.trim_start_matches('0').is_empty())
+}
+
+// The LS_COLORS key for an entry. Special permission bits beat being executable, which
+// beats the extension, same as ls.
+#[cfg(unix)]
+fn classify(meta: &Metadata) -> &'static str {
+    let file_type = meta.file_type();
+    let mode = meta.permissions().mode();
+
+    if file_type.is_symlink() {
+        "ln"
+    }
+    else if file_type.is_dir() {
+        match (mode & 0o1000 != 0, mode & 0o002 != 0) {
+            (true, true) => "tw",
+            (false, true) => "ow",
+            (true, false) => "st",
+            (false, false) => "di",
+        }
+    }
+    else if file_type.is_fifo() {
+        "pi"
+    }
+    else if file_type.is_socket() {
+        "so"
+    }
+    else if file_type.is_block_device() {
+        "bd"
+    }
+    else if file_type.is_char_device() {
+        "cd"
+    }
+    else if mode & 0o4000 != 0 {
+        "su"
+    }
+    else if mode & 0o2000 != 0 {
+        "sg"
+    }
+    else if mode & 0o111 != 0 {
+        "ex"
+    }
+    else {
+        "fi"
+    }
+}
+
+#[cfg(not(unix))]
+fn classify(meta: &Metadata) -> &'static str {
+    let file_type = meta.file_type();
+
+    if file_type.is_symlink() {
+        "ln"
+    }
+    else if file_type.is_dir() {
+        "di"
+    }
+    else {
+        "fi"
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        let colors = LsColors::parse("*.gz=01;31:*.tar.gz=01;32:*.JPG=35:*README=33:di=0:ln=:fi=00:ex=01;32:junk:=31");
+
+        // A longer suffix beats the plain extension, and extensions are matched ignoring case
+        assert_eq!(colors.name_style("backup.tar.gz"), Some("01;32"));
+        assert_eq!(colors.name_style("log.gz"), Some("01;31"));
+        assert_eq!(colors.name_style("photo.jpg"), Some("35"));
+        assert_eq!(colors.name_style("readme"), Some("33"));
+        assert_eq!(colors.name_style("notes.txt"), None);
+
+        // Zero or empty codes turn a type's colour off, instead of falling back to the defaults
+        assert_eq!(colors.type_style("di"), None);
+        assert_eq!(colors.type_style("ln"), None);
+        assert_eq!(colors.type_style("fi"), None);
+        assert_eq!(colors.type_style("ex"), Some("01;32"));
+
+        // Types that weren't mentioned keep the defaults
+        assert_eq!(colors.type_style("pi"), Some("40;33"));
+
+        assert!(is_visible("01;34"));
+        assert!(!is_visible("0"));
+        assert!(!is_visible("00;00"));
+        assert!(!is_visible(""));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn classifying() {
+        let path = env::temp_dir().join(format!("find-rs-test-{}-classify", std::process::id()));
+        fs::write(&path, b"").unwrap();
+
+        let class = |mode: u32| {
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+            classify(&fs::symlink_metadata(&path).unwrap())
+        };
+
+        // Setuid beats setgid, which beats being executable
+        let setuid = class(0o6755);
+        let setgid = class(0o2755);
+        let exec = class(0o755);
+        let plain = class(0o644);
+
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(setuid, "su");
+        assert_eq!(setgid, "sg");
+        assert_eq!(exec, "ex");
+        assert_eq!(plain, "fi");
+    }
+}
@@ -0,0 +1,391 @@
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use thread_pool::{ThreadPool, TaskResult};
+
+
+// Each one is replaced with part of the matched path, or of every path in the batch:
+// the whole path, its file name, and either of those without the extension
+static PLACEHOLDERS: &[&str] = &["{}", "{/}", "{.}", "{/.}"];
+
+// Room left over for anything the OS counts against ARG_MAX that we don't, like xargs does
+static ARG_MAX_HEADROOM: usize = 2048;
+
+// Used when the real limit can't be found. POSIX guarantees at least this much.
+static FALLBACK_ARG_MAX: usize = 4096;
+
+// What a shell would exit with for a command it couldn't run
+static SPAWN_FAILED_STATUS: i32 = 127;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecMode {
+    // Run the command once per match, like `find -exec cmd {} \;`
+    Each,
+    // Run it with as many matches as fit on the command line, like `find -exec cmd {} +`
+    Batch,
+}
+
+
+/// A command to run on matches, with `{}` standing in for the path(s). `{/}` is just the
+/// file name, and `{.}` and `{/.}` are those without the extension. Without any of them,
+/// the paths are added to the end of the command.
+#[derive(Debug, Clone)]
+pub struct CommandTemplate {
+    pub args: Vec<String>,
+    pub mode: ExecMode,
+}
+
+impl CommandTemplate {
+    pub fn new(args: Vec<String>, mode: ExecMode) -> Option<Self> {
+        match args.is_empty() {
+            true => None,
+            false => Some(Self {args, mode}),
+        }
+    }
+
+    fn has_placeholder(&self) -> bool {
+        self.args.iter().any(|arg| PLACEHOLDERS.iter().any(|placeholder| arg.contains(placeholder)))
+    }
+
+    /// The command line for a single path. Every placeholder is replaced, even inside a
+    /// larger argument (ex. `--out={.}.bak`).
+    fn build_each(&self, path: &Path) -> Vec<OsString> {
+        if !self.has_placeholder() {
+            let mut args: Vec<OsString> = self.args.iter().map(OsString::from).collect();
+            args.push(path.as_os_str().to_owned());
+            return args;
+        }
+
+        self.args.iter()
+            .map(|arg| replace_placeholders(arg, path))
+            .collect()
+    }
+
+    /// The command line for a batch of paths. A placeholder on its own expands into every
+    /// path, where anywhere else it can't sensibly be replaced, so it's left alone.
+    fn build_batch(&self, paths: &[PathBuf]) -> Vec<OsString> {
+        let mut args: Vec<OsString> = Vec::with_capacity(self.args.len() + paths.len());
+        let mut expanded = false;
+
+        for arg in &self.args {
+            match is_placeholder(arg) {
+                true => {
+                    args.extend(paths.iter().map(|path| expand_placeholder(arg, path)));
+                    expanded = true;
+                },
+                false => args.push(OsString::from(arg)),
+            }
+        }
+
+        if !expanded {
+            args.extend(paths.iter().map(|path| path.as_os_str().to_owned()));
+        }
+
+        args
+    }
+
+    /// Bytes the command takes up on its own, without any paths.
+    fn base_len(&self) -> usize {
+        self.args.iter()
+            .filter(|arg| !is_placeholder(arg))
+            .map(|arg| arg_len(arg.len()))
+            .sum()
+    }
+}
+
+fn is_placeholder(arg: &str) -> bool {
+    PLACEHOLDERS.contains(&arg)
+}
+
+fn expand_placeholder(placeholder: &str, path: &Path) -> OsString {
+    // A path like ".." has no file name, so it stands in for itself
+    let name = path.file_name().map(Path::new).unwrap_or(path);
+
+    match placeholder {
+        "{/}" => name.as_os_str().to_owned(),
+        "{.}" => path.with_extension("").into_os_string(),
+        "{/.}" => name.with_extension("").into_os_string(),
+        _ => path.as_os_str().to_owned(),
+    }
+}
+
+fn replace_placeholders(arg: &str, path: &Path) -> OsString {
+    let mut replaced = OsString::new();
+    let mut rest = arg;
+
+    while let Some(start) = rest.find('{') {
+        replaced.push(&rest[..start]);
+        rest = &rest[start..];
+
+        match PLACEHOLDERS.iter().find(|placeholder| rest.starts_with(*placeholder)) {
+            Some(placeholder) => {
+                replaced.push(expand_placeholder(placeholder, path));
+                rest = &rest[placeholder.len()..];
+            },
+            None => {
+                replaced.push("{");
+                rest = &rest[1..];
+            },
+        }
+    }
+
+    replaced.push(rest);
+    replaced
+}
+
+// Each argument costs its bytes, a NUL terminator, and a pointer in argv
+fn arg_len(len: usize) -> usize {
+    len + 1 + std::mem::size_of::<usize>()
+}
+
+#[cfg(unix)]
+fn arg_max() -> usize {
+    let limit = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+
+    let limit = match limit > 0 {
+        true => limit as usize,
+        false => FALLBACK_ARG_MAX,
+    };
+
+    // The environment is passed to the command too, and counts against the same limit
+    let env_len: usize = std::env::vars_os()
+        .map(|(key, val)| arg_len(key.len() + val.len() + 1))
+        .sum();
+
+    limit.saturating_sub(env_len + ARG_MAX_HEADROOM).max(FALLBACK_ARG_MAX)
+}
+
+#[cfg(not(unix))]
+fn arg_max() -> usize {
+    FALLBACK_ARG_MAX
+}
+
+
+/// Everything a single command printed, so it can be written out in one go instead of
+/// interleaving with commands running on other threads.
+#[derive(Debug)]
+pub struct ExecOutput {
+    pub status: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+fn run_command(args: Vec<OsString>) -> ExecOutput {
+    let output = Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .output();
+
+    match output {
+        // No exit code means it was killed by a signal
+        Ok(output) => ExecOutput {
+            status: output.status.code().unwrap_or(1),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        },
+        Err(err) => ExecOutput {
+            status: SPAWN_FAILED_STATUS,
+            stdout: vec![],
+            stderr: format!("find-rs: {}: {}\n", args[0].to_string_lossy(), err).into_bytes(),
+        },
+    }
+}
+
+
+/// Runs a command template on matches as they're found, spreading the commands across a
+/// thread pool.
+pub struct Executor {
+    template: CommandTemplate,
+    pool: ThreadPool<ExecOutput>,
+    // Paths waiting to go out in the next batch, and how much of ARG_MAX they'll use
+    batch: Vec<PathBuf>,
+    batch_len: usize,
+    arg_max: usize,
+    // The highest exit status seen so far
+    status: i32,
+}
+
+impl Executor {
+    pub fn new(template: CommandTemplate, workers: usize) -> Self {
+        Self {
+            batch_len: template.base_len(),
+            template,
+            pool: ThreadPool::create_pool(Some(workers)),
+            batch: vec![],
+            arg_max: arg_max(),
+            status: 0,
+        }
+    }
+
+    pub fn push(&mut self, path: PathBuf) {
+        match self.template.mode {
+            ExecMode::Each => {
+                let args = self.template.build_each(&path);
+                self.spawn(args);
+            },
+            ExecMode::Batch => {
+                let path_len = arg_len(path.as_os_str().len());
+
+                // Always send at least one path, even if it's too long on its own
+                if !self.batch.is_empty() && self.batch_len + path_len > self.arg_max {
+                    self.flush_batch();
+                }
+
+                self.batch_len += path_len;
+                self.batch.push(path);
+            },
+        }
+
+        // Print anything that's already done, so output shows up while we're still searching
+        while let Some(output) = self.pool.try_recv_result() {
+            self.write_output(output);
+        }
+    }
+
+    /// Runs whatever's batched up so far, and waits for every command to finish, without
+    /// ending the run. For when there won't be any more paths for a while.
+    pub fn flush(&mut self) {
+        self.flush_batch();
+
+        while let Some(output) = self.pool.recv_result() {
+            self.write_output(output);
+        }
+    }
+
+    /// Runs whatever's left, waits for every command to finish, and returns the exit status
+    /// for find-rs: 0 if every command succeeded, otherwise the highest status of any of them.
+    pub fn finish(mut self) -> i32 {
+        self.flush_batch();
+
+        while let Some(output) = self.pool.recv_result() {
+            self.write_output(output);
+        }
+
+        self.pool.join();
+        self.status
+    }
+
+    fn flush_batch(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+
+        let args = self.template.build_batch(&self.batch);
+        self.batch.clear();
+        self.batch_len = self.template.base_len();
+
+        self.spawn(args);
+    }
+
+    fn spawn(&mut self, args: Vec<OsString>) {
+        let spawned = self.pool.spawn(move || {
+            TaskResult::from_result(run_command(args)).to_opt()
+        });
+
+        if spawned.is_err() {
+            eprintln!("find-rs: Failed to queue command");
+            self.status = self.status.max(1);
+        }
+    }
+
+    fn write_output(&mut self, output: ExecOutput) {
+        self.status = self.status.max(output.status);
+
+        // Hold both locks so nothing else gets printed in the middle
+        let stdout = io::stdout();
+        let stderr = io::stderr();
+        let mut out = stdout.lock();
+        let mut err = stderr.lock();
+
+        // If stdout went away, there's nowhere left to send the output, but the commands
+        // still need to run.
+        out.write_all(&output.stdout).and_then(|_| out.flush()).ok();
+        err.write_all(&output.stderr).ok();
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn template(args: &[&str], mode: ExecMode) -> CommandTemplate {
+        CommandTemplate::new(args.iter().map(|arg| arg.to_string()).collect(), mode).unwrap()
+    }
+
+    fn os_args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn each_placeholders() {
+        let path = Path::new("src/dir/file.tar.gz");
+
+        let command = template(&["cp", "{}", "{/}", "{.}", "{/.}", "--out={.}.bak", "{x}"], ExecMode::Each);
+        assert_eq!(command.build_each(path), os_args(&[
+            "cp", "src/dir/file.tar.gz", "file.tar.gz", "src/dir/file.tar", "file.tar", "--out=src/dir/file.tar.bak", "{x}",
+        ]));
+
+        let command = template(&["echo", "-n"], ExecMode::Each);
+        assert_eq!(command.build_each(path), os_args(&["echo", "-n", "src/dir/file.tar.gz"]));
+    }
+
+    #[test]
+    fn batch_placeholders() {
+        let paths = vec![PathBuf::from("a/one.txt"), PathBuf::from("b/two")];
+
+        let command = template(&["tar", "-cf", "out.tar", "{}"], ExecMode::Batch);
+        assert_eq!(command.build_batch(&paths), os_args(&["tar", "-cf", "out.tar", "a/one.txt", "b/two"]));
+
+        let command = template(&["echo", "{/}", "--"], ExecMode::Batch);
+        assert_eq!(command.build_batch(&paths), os_args(&["echo", "one.txt", "two", "--"]));
+
+        let command = template(&["echo", "{.}"], ExecMode::Batch);
+        assert_eq!(command.build_batch(&paths), os_args(&["echo", "a/one", "b/two"]));
+
+        // Only a placeholder on its own expands, so these paths go on the end
+        let command = template(&["echo", "--in={}"], ExecMode::Batch);
+        assert_eq!(command.build_batch(&paths), os_args(&["echo", "--in={}", "a/one.txt", "b/two"]));
+    }
+
+    #[test]
+    fn batches_split_at_arg_max() {
+        let mut executor = Executor::new(template(&["true"], ExecMode::Batch), 2);
+        let path_len = arg_len("file-0".len());
+
+        // Room for the command and exactly two paths
+        executor.arg_max = executor.template.base_len() + path_len * 2;
+
+        executor.push(PathBuf::from("file-0"));
+        executor.push(PathBuf::from("file-1"));
+        assert_eq!(executor.batch.len(), 2);
+
+        executor.push(PathBuf::from("file-2"));
+        assert_eq!(executor.batch, vec![PathBuf::from("file-2")]);
+        assert_eq!(executor.batch_len, executor.template.base_len() + path_len);
+
+        // A path that doesn't fit on its own still gets sent by itself
+        executor.push(PathBuf::from("x".repeat(executor.arg_max)));
+        assert_eq!(executor.batch.len(), 1);
+
+        assert_eq!(executor.finish(), 0);
+    }
+
+    #[test]
+    fn finish_returns_worst_status() {
+        let command = template(&["sh", "-c", "exit \"$1\"", "sh", "{}"], ExecMode::Each);
+        let mut executor = Executor::new(command, 2);
+
+        for status in &["0", "3", "1"] {
+            executor.push(PathBuf::from(status));
+        }
+
+        assert_eq!(executor.finish(), 3);
+
+        let executor = Executor::new(template(&["true"], ExecMode::Each), 2);
+        assert_eq!(executor.finish(), 0);
+    }
+}
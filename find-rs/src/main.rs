@@ -1,7 +1,31 @@
 mod cli;
+mod colors;
+mod exec;
 mod lib;
+mod output;
+#[cfg(unix)]
+mod picker;
+mod template;
+mod tree;
 
-use finder::Finder;
+use std::io::{self, Write};
+use std::time::Instant;
+
+use finder::{DirUsage, DupeGroup, FileInfo, Finder};
+#[cfg(unix)]
+use finder::Index;
+#[cfg(target_os = "linux")]
+use finder::WatchEvent;
+
+use utils::pretty_fs_size;
+
+use cli::IndexAction;
+use colors::LsColors;
+use exec::Executor;
+use output::Printer;
+#[cfg(unix)]
+use picker::Picker;
+use tree::Tree;
 
 fn main() {
     let args = match cli::parse_cli() {
@@ -12,4 +36,364 @@ fn main() {
     if args.verbose {
         println!("{}", args);
     }
+
+    let start = Instant::now();
+
+    if let Some(action) = args.index_action {
+        write_index(&args, action, start);
+    }
+
+    // Answered from the index instead of walking, so there's no need for a Finder (or its threads)
+    if args.from_index {
+        search_index(&args, start);
+    }
+
+    let finder: Finder = args.finder_config().build();
+
+    if args.dupes {
+        let groups = finder.find_dupes(&args.dupe_finder());
+
+        if print_dupes(&mut io::stdout().lock(), &groups).is_err() {
+            std::process::exit(0);
+        }
+
+        if args.print_stats {
+            let wasted: u64 = groups.iter().map(DupeGroup::wasted).sum();
+
+            eprintln!(
+                "Found {} groups of duplicates wasting {} in {:.3}s",
+                groups.len(), pretty_fs_size(&(wasted as usize)), start.elapsed().as_secs_f64()
+            );
+        }
+
+        return;
+    }
+
+    if args.du {
+        let usage = finder.disk_usage(&args.disk_usage());
+
+        if print_usage(&mut io::stdout().lock(), &usage).is_err() {
+            std::process::exit(0);
+        }
+
+        if args.print_stats {
+            eprintln!("Totalled {} directories in {:.3}s", usage.len(), start.elapsed().as_secs_f64());
+        }
+
+        return;
+    }
+
+    if args.watch {
+        watch(&args, finder);
+    }
+
+    if args.pick {
+        pick(&args, finder);
+    }
+
+    // The whole tree has to be built before any of it can be printed
+    if args.tree {
+        let mut tree = Tree::new(args.root.clone())
+            .with_totals(args.tree_totals)
+            .with_colors(LsColors::for_stdout(&io::stdout()));
+        let mut n_found: usize = 0;
+
+        for file_info in finder.iter() {
+            tree.insert(&file_info);
+            n_found += 1;
+        }
+
+        if tree.render(&mut io::stdout().lock()).is_err() {
+            std::process::exit(0);
+        }
+
+        if args.print_stats {
+            eprintln!("Found {} matches in {:.3}s", n_found, start.elapsed().as_secs_f64());
+        }
+
+        return;
+    }
+
+    // With --order-by we have to see everything before printing anything, otherwise
+    // print each match as soon as a worker finds it. --limit alone just stops early.
+    match args.order_by.is_some() || args.limit.is_some() {
+        true => handle_results(&args, finder.collect(), start),
+        false => handle_results(&args, finder.iter(), start),
+    }
+}
+
+/// Prints (or runs --exec on) every result, then exits.
+fn handle_results<I>(args: &cli::FindArgs, results: I, start: Instant) -> !
+where
+    I: IntoIterator<Item = FileInfo>
+{
+    let stdout = io::stdout();
+    let colors = LsColors::for_stdout(&stdout);
+
+    // Highlighting is colour too, so it's only on when colours are
+    let highlight = match &args.fuzzy_matchers {
+        Some(queries) if colors.is_some() => queries.clone(),
+        _ => Vec::new(),
+    };
+
+    let mut printer = Printer::new(stdout.lock(), args.output_format, args.print0)
+        .with_template(args.format.clone())
+        .with_colors(colors)
+        .with_highlight(highlight);
+    let mut n_found: usize = 0;
+
+    // With --exec, matches are handed off to commands instead of being printed
+    let mut executor = args.exec.clone()
+        .map(|template| Executor::new(template, args.worker_threads));
+
+    let mut handle_match = |file_info: FileInfo| {
+        n_found += 1;
+
+        match &mut executor {
+            Some(executor) if !file_info.broken_link => executor.push(file_info.path),
+            // If stdout went away (ex. piped into `head`), there's no one left to print to.
+            _ => if printer.print(&file_info).is_err() {
+                std::process::exit(0);
+            },
+        }
+    };
+
+    results.into_iter().for_each(&mut handle_match);
+
+    if printer.finish().is_err() {
+        std::process::exit(0);
+    }
+
+    // Exit with the worst status of any command we ran
+    let status = executor.map(Executor::finish).unwrap_or(0);
+
+    if args.print_stats {
+        eprintln!("Found {} matches in {:.3}s", n_found, start.elapsed().as_secs_f64());
+    }
+
+    std::process::exit(status);
+}
+
+fn print_dupes<W: Write>(out: &mut W, groups: &[DupeGroup]) -> io::Result<()> {
+    for group in groups {
+        writeln!(
+            out, "{} copies of {} ({} wasted)",
+            group.files.len(), pretty_fs_size(&(group.size as usize)), pretty_fs_size(&(group.wasted() as usize))
+        )?;
+
+        for file_info in &group.files {
+            writeln!(out, "{}", file_info.path.display())?;
+        }
+
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+fn print_usage<W: Write>(out: &mut W, usage: &[DirUsage]) -> io::Result<()> {
+    for dir_usage in usage {
+        writeln!(
+            out, "{:>10}  {:>8}  {}",
+            pretty_fs_size(&(dir_usage.totals.size as usize)), dir_usage.totals.inodes, dir_usage.path.display()
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Builds or updates the index, then exits.
+#[cfg(unix)]
+fn write_index(args: &cli::FindArgs, action: IndexAction, start: Instant) -> ! {
+    // What to leave out (excludes, and filesystems it shouldn't go into) comes from the
+    // same config a search would use
+    let config = args.finder_config();
+
+    let index = match action {
+        IndexAction::Build => Index::build(&args.root, &config),
+        IndexAction::Update => Index::load(&args.index_file).map(|index| index.update(&config)),
+    };
+
+    let index = match index {
+        Ok(index) => index,
+        Err(err) => panic!("Could not index {}: {}", args.root.display(), err),
+    };
+
+    if let Err(err) = index.save(&args.index_file) {
+        panic!("Could not write the index to {}: {}", args.index_file.display(), err);
+    }
+
+    if args.print_stats {
+        eprintln!(
+            "Indexed {} entries in {} directories under {} in {:.3}s",
+            index.n_entries(), index.n_dirs(), index.root().display(), start.elapsed().as_secs_f64()
+        );
+    }
+
+    std::process::exit(0);
+}
+
+#[cfg(not(unix))]
+fn write_index(_args: &cli::FindArgs, _action: IndexAction, _start: Instant) -> ! {
+    panic!("--index is only supported on Unix");
+}
+
+/// Answers the search from the index instead of walking the filesystem. Nothing under the
+/// root is touched, so matches are as they were at the last update.
+#[cfg(unix)]
+fn search_index(args: &cli::FindArgs, start: Instant) -> ! {
+    let config = args.finder_config();
+
+    if let Some(needs) = Index::unsupported(&config) {
+        panic!("Could not search the index: it doesn't know about {}, search without --from-index instead", needs);
+    }
+
+    let search = match Index::search(&args.index_file, &config, &args.root) {
+        Ok(search) => search,
+        Err(err) => panic!("Could not read the index at {}: {}", args.index_file.display(), err),
+    };
+
+    let results = search.map(|found| match found {
+        Ok(found) => found,
+        Err(err) => panic!("Could not read the index at {}: {}", args.index_file.display(), err),
+    });
+
+    let stdout = io::stdout();
+    let colors = LsColors::for_stdout(&stdout);
+
+    let highlight = match &args.fuzzy_matchers {
+        Some(queries) if colors.is_some() => queries.clone(),
+        _ => Vec::new(),
+    };
+
+    let mut printer = Printer::new(stdout.lock(), args.output_format, args.print0)
+        .with_colors(colors)
+        .with_highlight(highlight);
+    let mut n_found: usize = 0;
+
+    let mut executor = args.exec.clone()
+        .map(|template| Executor::new(template, args.worker_threads));
+
+    for found in Index::arrange(&config, results) {
+        n_found += 1;
+
+        match &mut executor {
+            Some(executor) => executor.push(found.path),
+            None => if printer.print_indexed(&found).is_err() {
+                std::process::exit(0);
+            },
+        }
+    }
+
+    if printer.finish().is_err() {
+        std::process::exit(0);
+    }
+
+    let status = executor.map(Executor::finish).unwrap_or(0);
+
+    if args.print_stats {
+        eprintln!("Found {} matches in {:.3}s", n_found, start.elapsed().as_secs_f64());
+    }
+
+    std::process::exit(status);
+}
+
+#[cfg(not(unix))]
+fn search_index(_args: &cli::FindArgs, _start: Instant) -> ! {
+    panic!("--from-index is only supported on Unix");
+}
+
+/// Runs the fuzzy picker over the results, prints whatever was chosen, then exits.
+#[cfg(unix)]
+fn pick(args: &cli::FindArgs, finder: Finder) -> ! {
+    let picker = Picker::new(args.root.clone())
+        .with_query(args.query.clone().unwrap_or_default());
+
+    let chosen = match picker.pick(finder.iter()) {
+        Ok(Some(chosen)) => chosen,
+        // Cancelled, same as fzf
+        Ok(None) => std::process::exit(130),
+        Err(err) => panic!("Could not open the terminal: {}", err),
+    };
+
+    let terminator = if args.print0 {b'\0'} else {b'\n'};
+    let mut stdout = io::stdout();
+
+    for path in &chosen {
+        let written = stdout.write_all(&output::path_bytes(path)).and_then(|_| stdout.write_all(&[terminator]));
+
+        if written.is_err() {
+            std::process::exit(0);
+        }
+    }
+
+    std::process::exit(if chosen.is_empty() {1} else {0});
+}
+
+#[cfg(not(unix))]
+fn pick(_args: &cli::FindArgs, _finder: Finder) -> ! {
+    panic!("--pick is only supported on Unix");
+}
+
+/// Prints the initial matches, then changes as they happen, until killed.
+#[cfg(target_os = "linux")]
+fn watch(args: &cli::FindArgs, finder: Finder) -> ! {
+    let mut watcher = match finder.watch() {
+        Ok(watcher) => watcher,
+        Err(err) => panic!("Could not start watching: {}", err),
+    };
+
+    let mut executor = args.exec.clone()
+        .map(|template| Executor::new(template, args.worker_threads));
+
+    let terminator = if args.print0 {b'\0'} else {b'\n'};
+    let mut events = watcher.start();
+
+    loop {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+
+        for event in events {
+            let (prefix, path) = match event {
+                WatchEvent::Matched(file_info) => match &mut executor {
+                    Some(executor) => {
+                        executor.push(file_info.path);
+                        continue;
+                    },
+                    None => ("+ ", file_info.path),
+                },
+                WatchEvent::Unmatched(_) if executor.is_some() => continue,
+                WatchEvent::Unmatched(path) => ("- ", path),
+            };
+
+            let written = out.write_all(prefix.as_bytes())
+                .and_then(|_| out.write_all(&output::path_bytes(&path)))
+                .and_then(|_| out.write_all(&[terminator]));
+
+            if written.is_err() {
+                std::process::exit(0);
+            }
+        }
+
+        // Nothing else happens until the next change, so don't leave anything waiting
+        if out.flush().is_err() {
+            std::process::exit(0);
+        }
+
+        drop(out);
+
+        if let Some(executor) = &mut executor {
+            executor.flush();
+        }
+
+        events = match watcher.wait() {
+            Ok(events) => events,
+            Err(err) => panic!("Could not read changes: {}", err),
+        };
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn watch(_args: &cli::FindArgs, _finder: Finder) -> ! {
+    panic!("--watch is only supported on Linux");
 }
@@ -0,0 +1,406 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use finder::{fuzzy_match_path, FileInfo, MatchedLine};
+#[cfg(unix)]
+use finder::{EntryKind, IndexMatch};
+
+use crate::colors::{self, LsColors};
+use crate::template::Template;
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+
+static CSV_HEADER: &str = "path,size,mtime,mode,uid,inode,type";
+
+// Added on top of a character's colour when it was matched by a fuzzy query
+static HIGHLIGHT: &str = "01;04";
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    // One path per line, or grep style lines for content matches
+    Plain,
+    // A single array holding every match
+    Json,
+    // One object per line
+    JsonLines,
+    Csv,
+    // Nothing at all, for when only the exit status or --stats matter
+    Null,
+}
+
+
+/// The metadata printed for each match by the structured formats. Fields that the
+/// platform doesn't have are left empty (or null).
+struct Record {
+    path: String,
+    size: u64,
+    // Seconds since the unix epoch
+    mtime: Option<i64>,
+    // Permission bits only, without the file type
+    mode: Option<u32>,
+    uid: Option<u32>,
+    inode: Option<u64>,
+    file_type: &'static str,
+}
+
+impl Record {
+    fn new(file_info: &FileInfo) -> Self {
+        let meta = &file_info.meta;
+        let file_type = meta.file_type();
+
+        let file_type = if file_type.is_dir() {"dir"}
+            else if file_type.is_file() {"file"}
+            else if file_type.is_symlink() {"symlink"}
+            else {"other"};
+
+        let (mtime, mode, uid, inode) = unix_fields(file_info);
+
+        Record {
+            path: file_info.path.to_string_lossy().into_owned(),
+            size: meta.len(),
+            mtime,
+            mode,
+            uid,
+            inode,
+            file_type,
+        }
+    }
+
+    // Only what the index keeps, so there's no mode or owner
+    #[cfg(unix)]
+    fn from_index(found: &IndexMatch) -> Self {
+        let file_type = match found.stat.kind {
+            EntryKind::Dir => "dir",
+            EntryKind::File => "file",
+            EntryKind::Symlink => "symlink",
+            EntryKind::Other => "other",
+        };
+
+        Record {
+            path: found.path.to_string_lossy().into_owned(),
+            size: found.stat.size,
+            mtime: Some(found.stat.mtime.0),
+            mode: None,
+            uid: None,
+            inode: Some(found.stat.inode),
+            file_type,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "path": self.path,
+            "size": self.size,
+            "mtime": self.mtime,
+            "mode": self.mode,
+            "uid": self.uid,
+            "inode": self.inode,
+            "type": self.file_type,
+        })
+    }
+
+    fn to_csv(&self) -> String {
+        fn opt<T: ToString>(val: Option<T>) -> String {
+            val.map(|val| val.to_string()).unwrap_or_default()
+        }
+
+        format!(
+            "{},{},{},{},{},{},{}",
+            csv_escape(&self.path), self.size, opt(self.mtime), opt(self.mode),
+            opt(self.uid), opt(self.inode), self.file_type,
+        )
+    }
+}
+
+#[cfg(unix)]
+fn unix_fields(file_info: &FileInfo) -> (Option<i64>, Option<u32>, Option<u32>, Option<u64>) {
+    let meta = &file_info.meta;
+    (Some(meta.mtime()), Some(meta.mode() & 0o7777), Some(meta.uid()), Some(meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn unix_fields(file_info: &FileInfo) -> (Option<i64>, Option<u32>, Option<u32>, Option<u64>) {
+    let mtime = file_info.meta.modified().ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|since| since.as_secs() as i64);
+
+    (mtime, None, None, None)
+}
+
+#[cfg(unix)]
+pub(crate) fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+// Quotes a field if it needs it, doubling any quotes inside, as in RFC 4180
+fn csv_escape(field: &str) -> String {
+    match field.contains(&[',', '"', '\n', '\r'][..]) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+
+/// Writes matches to `out` in the chosen format. `finish` has to be called once everything's
+/// been printed, since some formats need closing off.
+pub struct Printer<W: Write> {
+    out: W,
+    format: OutputFormat,
+    // Separate plain paths with NUL instead of a newline
+    print0: bool,
+    // Replaces the plain output when set
+    template: Option<Template>,
+    // Colours plain paths by file type when set
+    colors: Option<LsColors>,
+    // Fuzzy queries whose matched characters get highlighted in plain paths
+    highlight: Vec<String>,
+    n_printed: usize,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(out: W, format: OutputFormat, print0: bool) -> Self {
+        Self {out, format, print0, template: None, colors: None, highlight: Vec::new(), n_printed: 0}
+    }
+
+    pub fn with_template(mut self, template: Option<Template>) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// Colours each plain path, with the parent directories as a directory and the name by
+    /// its type. Only meant for a terminal, the escape codes would end up in anything else.
+    pub fn with_colors(mut self, colors: Option<LsColors>) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// Highlights the characters of each path matched by the best scoring of these fuzzy
+    /// queries, on top of any colours. Like colours, only meant for a terminal.
+    pub fn with_highlight(mut self, queries: Vec<String>) -> Self {
+        self.highlight = queries;
+        self
+    }
+
+    pub fn print(&mut self, file_info: &FileInfo) -> io::Result<()> {
+        // Broken links aren't really matches, so keep them out of the results. They're still
+        // coloured like any other path, so they stand out as orphans.
+        if file_info.broken_link {
+            let name_style = self.colors.as_ref().and_then(|colors| colors.style(file_info));
+            eprintln!("Broken link - {}", self.styled_path(&file_info.path, name_style));
+            return Ok(());
+        }
+
+        match self.format {
+            OutputFormat::Plain => self.print_plain(file_info)?,
+            _ => self.print_record(Record::new(file_info))?,
+        }
+
+        self.n_printed += 1;
+        Ok(())
+    }
+
+    /// Prints a match found in the index. Templates aren't supported, since they can ask for
+    /// more than the index keeps.
+    #[cfg(unix)]
+    pub fn print_indexed(&mut self, found: &IndexMatch) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Plain => {
+                let name_style = self.colors.as_ref().and_then(|colors| colors.kind_style(&found.path, found.stat.kind));
+                let path = self.styled_path(&found.path, name_style);
+
+                match self.print0 {
+                    true => {
+                        self.out.write_all(&path_bytes(&found.path))?;
+                        self.out.write_all(b"\0")?;
+                    },
+                    false => writeln!(self.out, "{}", path)?,
+                }
+            },
+            _ => self.print_record(Record::from_index(found))?,
+        }
+
+        self.n_printed += 1;
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        match (self.format, self.n_printed) {
+            (OutputFormat::Json, 0) => writeln!(self.out, "[]")?,
+            (OutputFormat::Json, _) => writeln!(self.out, "\n]")?,
+            (OutputFormat::Csv, 0) => writeln!(self.out, "{}", CSV_HEADER)?,
+            _ => (),
+        }
+
+        self.out.flush()
+    }
+
+    // Everything but the plain format
+    fn print_record(&mut self, record: Record) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Json => {
+                let sep = if self.n_printed == 0 {"[\n"} else {",\n"};
+                write!(self.out, "{}  {}", sep, record.to_json())
+            },
+            OutputFormat::JsonLines => writeln!(self.out, "{}", record.to_json()),
+            OutputFormat::Csv => {
+                if self.n_printed == 0 {
+                    writeln!(self.out, "{}", CSV_HEADER)?;
+                }
+
+                writeln!(self.out, "{}", record.to_csv())
+            },
+            OutputFormat::Plain | OutputFormat::Null => Ok(()),
+        }
+    }
+
+    fn print_plain(&mut self, file_info: &FileInfo) -> io::Result<()> {
+        if let Some(template) = &self.template {
+            let terminator = if self.print0 {'\0'} else {'\n'};
+            return write!(self.out, "{}{}", template.render(file_info), terminator);
+        }
+
+        // Written as raw bytes, so names that aren't valid UTF-8 survive the trip
+        if self.print0 {
+            self.out.write_all(&path_bytes(&file_info.path))?;
+            return self.out.write_all(b"\0");
+        }
+
+        let name_style = self.colors.as_ref().and_then(|colors| colors.style(file_info));
+        let path = self.styled_path(&file_info.path, name_style);
+
+        match &file_info.content_matches {
+            Some(lines) => write_content_matches(&mut self.out, &path, lines),
+            None => writeln!(self.out, "{}", path),
+        }
+    }
+
+    // The path with any colours and highlighting. Each character gets the colour for its
+    // part of the path, plus the highlight if it was matched, and runs of characters with
+    // the same style share one escape code.
+    fn styled_path(&self, file_path: &Path, name_style: Option<&str>) -> String {
+        let path = file_path.to_string_lossy();
+        let dir_style = self.colors.as_ref().and_then(LsColors::dir_style);
+
+        let positions = self.highlight.iter()
+            .filter_map(|query| fuzzy_match_path(file_path, query))
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, positions)| positions)
+            .unwrap_or_default();
+
+        if dir_style.is_none() && name_style.is_none() && positions.is_empty() {
+            return path.into_owned();
+        }
+
+        let name_len = file_path.file_name()
+            .map(|name| name.to_string_lossy().chars().count())
+            .unwrap_or(0);
+        let name_start = path.chars().count() - name_len;
+
+        let mut positions = positions.into_iter().peekable();
+        let mut styled = String::new();
+        let mut run = String::new();
+        let mut run_style: Option<String> = None;
+
+        for (char_idx, c) in path.chars().enumerate() {
+            let base = if char_idx < name_start {dir_style} else {name_style};
+
+            let style = match positions.next_if_eq(&char_idx) {
+                Some(_) => Some(base.map(|base| format!("{};{}", base, HIGHLIGHT)).unwrap_or_else(|| HIGHLIGHT.to_string())),
+                None => base.map(String::from),
+            };
+
+            if style != run_style {
+                styled.push_str(&colors::paint(&run, run_style.as_deref()));
+                run.clear();
+                run_style = style;
+            }
+
+            run.push(c);
+        }
+
+        styled.push_str(&colors::paint(&run, run_style.as_deref()));
+        styled
+    }
+}
+
+// Same layout as grep: "path:line:text" for matches, "path-line-text" for context, and
+// "--" between groups of lines that aren't next to each other.
+fn write_content_matches<W: Write>(out: &mut W, path: &str, lines: &[MatchedLine]) -> io::Result<()> {
+    let mut last_line: Option<usize> = None;
+
+    for line in lines {
+        if let Some(last) = last_line {
+            if line.line_number > last + 1 {
+                writeln!(out, "--")?;
+            }
+        }
+
+        let sep = if line.is_context {'-'} else {':'};
+        writeln!(out, "{}{}{}{}{}", path, sep, line.line_number, sep, line.text)?;
+
+        last_line = Some(line.line_number);
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+
+    fn file_info(path: &str) -> FileInfo {
+        FileInfo::new(path.into(), fs::metadata(env::temp_dir()).unwrap(), 1)
+    }
+
+    fn print_json(paths: &[&str]) -> String {
+        let mut printer = Printer::new(Vec::new(), OutputFormat::Json, false);
+
+        for path in paths {
+            printer.print(&file_info(path)).unwrap();
+        }
+
+        printer.finish().unwrap();
+        String::from_utf8(printer.out).unwrap()
+    }
+
+    #[test]
+    fn csv_quoting() {
+        assert_eq!(csv_escape("plain/path.txt"), "plain/path.txt");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_escape("carriage\rreturn"), "\"carriage\rreturn\"");
+    }
+
+    #[test]
+    fn json_array_framing() {
+        assert_eq!(print_json(&[]), "[]\n");
+
+        let printed = print_json(&["one", "two", "three"]);
+        assert!(printed.starts_with("[\n  {"));
+        assert!(printed.ends_with("}\n]\n"));
+        assert_eq!(printed.matches("},\n  {").count(), 2);
+
+        let parsed: Value = serde_json::from_str(&printed).unwrap();
+        let paths: Vec<&str> = parsed.as_array().unwrap().iter()
+            .map(|record| record["path"].as_str().unwrap())
+            .collect();
+
+        assert_eq!(paths, vec!["one", "two", "three"]);
+    }
+}
@@ -0,0 +1,480 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use termion::{clear, color, cursor, style};
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+
+use finder::{fuzzy_match, FileInfo};
+
+
+// The prompt and status lines above the list
+static HEADER_LINES: usize = 2;
+
+// How many queued events get handled before redrawing, so a fast walk doesn't turn into
+// a redraw for every file
+static MAX_EVENT_BATCH: usize = 4096;
+
+
+enum Event {
+    Key(Key),
+    Found(PathBuf),
+    WalkDone,
+}
+
+
+struct Entry {
+    path: PathBuf,
+    // What's shown and matched against, the path relative to the root
+    display: String,
+}
+
+struct Ranked {
+    idx: usize,
+    score: isize,
+    // Char indexes into the entry's display string
+    positions: Vec<usize>,
+}
+
+
+/// A full screen fuzzy finder, like fzf. Results are added to the list as the search finds
+/// them, and re-ranked against the query on every keystroke. Tab selects more than one entry.
+pub struct Picker {
+    root: PathBuf,
+    query: String,
+}
+
+impl Picker {
+    pub fn new(root: PathBuf) -> Self {
+        Self {root, query: String::new()}
+    }
+
+    pub fn with_query(mut self, query: String) -> Self {
+        self.query = query;
+        self
+    }
+
+    /// Runs the picker on the terminal until the user accepts or backs out. Returns the
+    /// chosen paths, or None if the user cancelled. The UI is drawn on /dev/tty, so stdout
+    /// is free to be piped somewhere else.
+    pub fn pick<I>(self, results: I) -> io::Result<Option<Vec<PathBuf>>>
+    where
+        I: Iterator<Item = FileInfo> + Send + 'static
+    {
+        let tty_in = termion::get_tty()?;
+        let tty_out = termion::get_tty()?.into_raw_mode()?;
+        let size_fd = termion::get_tty()?;
+        let mut screen = AlternateScreen::from(tty_out);
+
+        let events = spawn_event_threads(tty_in, results);
+        let mut state = PickerState::new(self.root, self.query);
+
+        loop {
+            state.draw(&mut screen, tty_size(&size_fd))?;
+
+            let event = match events.recv() {
+                Ok(event) => event,
+                // Both threads are gone, which only happens if the tty can't be read
+                Err(_) => return Ok(None),
+            };
+
+            let batch = std::iter::once(event).chain(events.try_iter().take(MAX_EVENT_BATCH));
+
+            for event in batch {
+                if let Some(outcome) = state.handle(event) {
+                    return Ok(outcome);
+                }
+            }
+
+            state.finish_batch();
+        }
+    }
+}
+
+
+fn spawn_event_threads<I>(tty_in: File, results: I) -> Receiver<Event>
+where
+    I: Iterator<Item = FileInfo> + Send + 'static
+{
+    let (sender, receiver) = mpsc::channel();
+    let key_sender = sender.clone();
+
+    // Once the receiver is dropped the send fails, and dropping the results stops the search
+    thread::spawn(move || {
+        for file_info in results {
+            if sender.send(Event::Found(file_info.path)).is_err() {
+                return;
+            }
+        }
+
+        sender.send(Event::WalkDone).ok();
+    });
+
+    thread::spawn(move || {
+        for key in tty_in.keys() {
+            match key {
+                Ok(key) if key_sender.send(Event::Key(key)).is_ok() => (),
+                _ => return,
+            }
+        }
+    });
+
+    receiver
+}
+
+
+struct PickerState {
+    root: PathBuf,
+    query: String,
+    entries: Vec<Entry>,
+    // Entries matching the query, best first
+    ranked: Vec<Ranked>,
+    // Whether anything was added to `ranked` since it was last sorted
+    unsorted: bool,
+    // Index into `ranked`
+    cursor: usize,
+    // The first entry of `ranked` that's on screen
+    scroll: usize,
+    // Indexes into `entries`
+    selected: HashSet<usize>,
+    walking: bool,
+}
+
+impl PickerState {
+    fn new(root: PathBuf, query: String) -> Self {
+        Self {
+            root,
+            query,
+            entries: Vec::new(),
+            ranked: Vec::new(),
+            unsorted: false,
+            cursor: 0,
+            scroll: 0,
+            selected: HashSet::new(),
+            walking: true,
+        }
+    }
+
+    /// Returns Some once the picker is finished, holding the chosen paths, or None if the
+    /// user cancelled.
+    fn handle(&mut self, event: Event) -> Option<Option<Vec<PathBuf>>> {
+        match event {
+            Event::Found(path) => self.add(path),
+            Event::WalkDone => self.walking = false,
+            Event::Key(key) => return self.handle_key(key),
+        }
+
+        None
+    }
+
+    fn handle_key(&mut self, key: Key) -> Option<Option<Vec<PathBuf>>> {
+        match key {
+            Key::Char('\n') => return Some(Some(self.chosen())),
+            Key::Esc | Key::Ctrl('c') | Key::Ctrl('g') => return Some(None),
+            Key::Char('\t') => {
+                self.toggle_selected();
+                self.move_cursor(1);
+            },
+            Key::BackTab => {
+                self.toggle_selected();
+                self.move_cursor(-1);
+            },
+            Key::Up | Key::Ctrl('p') => self.move_cursor(-1),
+            Key::Down | Key::Ctrl('n') => self.move_cursor(1),
+            Key::PageUp => self.move_cursor(-10),
+            Key::PageDown => self.move_cursor(10),
+            Key::Backspace => {
+                self.query.pop();
+                self.rerank(false);
+            },
+            Key::Ctrl('u') => {
+                self.query.clear();
+                self.rerank(false);
+            },
+            Key::Ctrl('w') => {
+                let trimmed_len = self.query.trim_end().len();
+                let word_start = self.query[..trimmed_len].rfind(char::is_whitespace).map(|idx| idx + 1).unwrap_or(0);
+
+                self.query.truncate(word_start);
+                self.rerank(false);
+            },
+            Key::Char(c) if !c.is_control() => {
+                self.query.push(c);
+                // Anything that doesn't match the shorter query can't match a longer one
+                self.rerank(true);
+            },
+            _ => (),
+        }
+
+        None
+    }
+
+    fn add(&mut self, path: PathBuf) {
+        let display = match path.strip_prefix(&self.root) {
+            Ok(rel_path) => rel_path.to_string_lossy().into_owned(),
+            Err(_) => path.to_string_lossy().into_owned(),
+        };
+
+        let idx = self.entries.len();
+
+        if let Some(ranked) = rank(&self.query, idx, &display) {
+            self.ranked.push(ranked);
+            self.unsorted = true;
+        }
+
+        self.entries.push(Entry {path, display});
+    }
+
+    fn finish_batch(&mut self) {
+        if self.unsorted {
+            sort_ranked(&mut self.ranked);
+            self.unsorted = false;
+        }
+    }
+
+    fn rerank(&mut self, narrow: bool) {
+        let candidates: Vec<usize> = match narrow {
+            true => self.ranked.iter().map(|ranked| ranked.idx).collect(),
+            false => (0..self.entries.len()).collect(),
+        };
+
+        self.ranked = candidates.into_iter()
+            .filter_map(|idx| rank(&self.query, idx, &self.entries[idx].display))
+            .collect();
+
+        sort_ranked(&mut self.ranked);
+        self.unsorted = false;
+        self.cursor = 0;
+        self.scroll = 0;
+    }
+
+    fn move_cursor(&mut self, by: isize) {
+        if self.ranked.is_empty() {
+            return;
+        }
+
+        let max = self.ranked.len() as isize - 1;
+        self.cursor = (self.cursor as isize + by).clamp(0, max) as usize;
+    }
+
+    fn toggle_selected(&mut self) {
+        if let Some(ranked) = self.ranked.get(self.cursor) {
+            if !self.selected.remove(&ranked.idx) {
+                self.selected.insert(ranked.idx);
+            }
+        }
+    }
+
+    // Everything selected in the order it was found, or just the entry under the cursor
+    fn chosen(&self) -> Vec<PathBuf> {
+        if self.selected.is_empty() {
+            return self.ranked.get(self.cursor)
+                .map(|ranked| vec![self.entries[ranked.idx].path.clone()])
+                .unwrap_or_default();
+        }
+
+        let mut selected: Vec<usize> = self.selected.iter().copied().collect();
+        selected.sort_unstable();
+
+        selected.into_iter()
+            .map(|idx| self.entries[idx].path.clone())
+            .collect()
+    }
+
+    // The whole frame is built up first and written in one go, so it doesn't flicker
+    fn draw<W: Write>(&mut self, out: &mut W, (width, height): (usize, usize)) -> io::Result<()> {
+        let list_height = height.saturating_sub(HEADER_LINES).max(1);
+
+        self.cursor = self.cursor.min(self.ranked.len().saturating_sub(1));
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        }
+        else if self.cursor >= self.scroll + list_height {
+            self.scroll = self.cursor + 1 - list_height;
+        }
+
+        let mut frame: Vec<u8> = Vec::new();
+
+        write!(frame, "{}{}{}> {}{}", cursor::Goto(1, 1), clear::CurrentLine, style::Bold, style::Reset, self.query)?;
+
+        write!(frame, "{}{}", cursor::Goto(1, 2), clear::CurrentLine)?;
+        write!(frame, "{}  {}/{}", color::Fg(color::LightBlack), self.ranked.len(), self.entries.len())?;
+        if !self.selected.is_empty() {
+            write!(frame, " ({} selected)", self.selected.len())?;
+        }
+        if self.walking {
+            write!(frame, " searching...")?;
+        }
+        write!(frame, "{}", color::Fg(color::Reset))?;
+
+        let visible = self.ranked.iter().enumerate().skip(self.scroll).take(list_height);
+
+        for (row, (rank_idx, ranked)) in visible.enumerate() {
+            write!(frame, "{}{}", cursor::Goto(1, (row + HEADER_LINES + 1) as u16), clear::CurrentLine)?;
+
+            match rank_idx == self.cursor {
+                true => write!(frame, "{}{}>{}", style::Bold, color::Fg(color::Red), color::Fg(color::Reset))?,
+                false => write!(frame, " ")?,
+            }
+
+            match self.selected.contains(&ranked.idx) {
+                true => write!(frame, "{}*{} ", color::Fg(color::Magenta), color::Fg(color::Reset))?,
+                false => write!(frame, "  ")?,
+            }
+
+            // Three columns for the markers
+            let display = &self.entries[ranked.idx].display;
+            write_highlighted(&mut frame, display, &ranked.positions, width.saturating_sub(3))?;
+
+            write!(frame, "{}", style::Reset)?;
+        }
+
+        let n_drawn = self.ranked.len().saturating_sub(self.scroll).min(list_height);
+        write!(frame, "{}{}", cursor::Goto(1, (n_drawn + HEADER_LINES + 1) as u16), clear::AfterCursor)?;
+
+        // Leave the cursor at the end of the query
+        let query_col = 3 + self.query.chars().count();
+        write!(frame, "{}", cursor::Goto(query_col.min(width).max(1) as u16, 1))?;
+
+        out.write_all(&frame)?;
+        out.flush()
+    }
+}
+
+
+fn rank(query: &str, idx: usize, display: &str) -> Option<Ranked> {
+    if query.is_empty() {
+        return Some(Ranked {idx, score: 0, positions: Vec::new()});
+    }
+
+    fuzzy_match(query, display).map(|(score, positions)| Ranked {idx, score, positions})
+}
+
+// Best score first, with ties going to whatever was found first
+fn sort_ranked(ranked: &mut [Ranked]) {
+    ranked.sort_by(|a, b| b.score.cmp(&a.score).then(a.idx.cmp(&b.idx)));
+}
+
+fn write_highlighted<W: Write>(out: &mut W, display: &str, positions: &[usize], max_chars: usize) -> io::Result<()> {
+    let mut positions = positions.iter().peekable();
+
+    for (char_idx, c) in display.chars().take(max_chars).enumerate() {
+        match positions.peek() {
+            Some(&&pos) if pos == char_idx => {
+                positions.next();
+                write!(out, "{}{}{}", color::Fg(color::Green), c, color::Fg(color::Reset))?;
+            },
+            _ => write!(out, "{}", c)?,
+        }
+    }
+
+    Ok(())
+}
+
+// termion's terminal_size() asks stdout, which is usually a pipe when picking
+fn tty_size(tty: &File) -> (usize, usize) {
+    use std::os::unix::io::AsRawFd;
+
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+
+    match unsafe { libc::ioctl(tty.as_raw_fd(), libc::TIOCGWINSZ, &mut size) } {
+        0 if size.ws_col > 0 && size.ws_row > 0 => (size.ws_col as usize, size.ws_row as usize),
+        _ => (80, 24),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn state(paths: &[&str]) -> PickerState {
+        let mut state = PickerState::new("/root".into(), String::new());
+
+        for path in paths {
+            state.handle(Event::Found(PathBuf::from("/root").join(path)));
+        }
+
+        state.finish_batch();
+        state
+    }
+
+    fn type_keys(state: &mut PickerState, keys: &[Key]) {
+        for key in keys {
+            assert!(state.handle(Event::Key(*key)).is_none());
+        }
+    }
+
+    fn ranked_displays(state: &PickerState) -> Vec<&str> {
+        state.ranked.iter().map(|ranked| state.entries[ranked.idx].display.as_str()).collect()
+    }
+
+    #[test]
+    fn narrowing() {
+        let mut state = state(&["src/main.rs", "src/lib.rs", "README.md", "src/picker.rs"]);
+        assert_eq!(state.ranked.len(), 4);
+
+        type_keys(&mut state, &[Key::Char('s'), Key::Char('r'), Key::Char('c')]);
+        let mut narrowed = ranked_displays(&state);
+        narrowed.sort_unstable();
+        assert_eq!(narrowed, vec!["src/lib.rs", "src/main.rs", "src/picker.rs"]);
+
+        type_keys(&mut state, &[Key::Char('p')]);
+        assert_eq!(ranked_displays(&state), vec!["src/picker.rs"]);
+
+        // Backing up widens the search out again, including entries the narrower query dropped
+        type_keys(&mut state, &[Key::Backspace, Key::Backspace, Key::Backspace, Key::Backspace]);
+        assert_eq!(state.ranked.len(), 4);
+
+        // Found while a query is typed in, so only kept if it matches
+        type_keys(&mut state, &[Key::Char('m'), Key::Char('d')]);
+        state.handle(Event::Found("/root/docs/guide.md".into()));
+        state.handle(Event::Found("/root/build.rs".into()));
+        state.finish_batch();
+
+        let mut found = ranked_displays(&state);
+        found.sort_unstable();
+        assert_eq!(found, vec!["README.md", "docs/guide.md"]);
+    }
+
+    #[test]
+    fn chosen_order() {
+        let mut state = state(&["one", "two", "three"]);
+
+        // Nothing selected, so just the entry under the cursor
+        type_keys(&mut state, &[Key::Down]);
+        assert_eq!(state.chosen(), vec![PathBuf::from("/root/two")]);
+
+        // Selected bottom up, but chosen in the order they were found
+        type_keys(&mut state, &[Key::Down, Key::Char('\t'), Key::Up, Key::Up, Key::Char('\t')]);
+        assert_eq!(state.chosen(), vec![PathBuf::from("/root/one"), PathBuf::from("/root/three")]);
+
+        // Tab again unselects
+        type_keys(&mut state, &[Key::Down, Key::Char('\t')]);
+        assert_eq!(state.chosen(), vec![PathBuf::from("/root/one")]);
+
+        assert_eq!(state.handle(Event::Key(Key::Char('\n'))), Some(Some(vec![PathBuf::from("/root/one")])));
+        assert_eq!(state.handle(Event::Key(Key::Esc)), Some(None));
+    }
+
+    #[test]
+    fn delete_word() {
+        let mut state = state(&[]);
+
+        state.query = "src main  ".to_string();
+        type_keys(&mut state, &[Key::Ctrl('w')]);
+        assert_eq!(state.query, "src ");
+
+        type_keys(&mut state, &[Key::Ctrl('w')]);
+        assert_eq!(state.query, "");
+
+        type_keys(&mut state, &[Key::Ctrl('w')]);
+        assert_eq!(state.query, "");
+    }
+}
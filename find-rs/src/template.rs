@@ -0,0 +1,262 @@
+use std::fmt;
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local};
+use chrono::format::{Item, StrftimeItems};
+
+use finder::FileInfo;
+
+use utils::pretty_fs_size;
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+
+// Used for a plain {mtime}
+static DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Placeholder {
+    Path,
+    Name,
+    Stem,
+    Ext,
+    Parent,
+    Size,
+    // Size as a human readable string, ex. "4.2 MiB"
+    SizeHuman,
+    // A strftime style format string
+    Mtime(String),
+    Mode,
+    Depth,
+    Score,
+}
+
+impl Placeholder {
+    fn parse(spec: &str) -> Result<Self, String> {
+        let (name, arg) = match spec.find(':') {
+            Some(idx) => (&spec[..idx], Some(&spec[idx + 1..])),
+            None => (spec, None),
+        };
+
+        let placeholder = match (name, arg) {
+            ("path", None) => Placeholder::Path,
+            ("name", None) => Placeholder::Name,
+            ("stem", None) => Placeholder::Stem,
+            ("ext", None) => Placeholder::Ext,
+            ("parent", None) => Placeholder::Parent,
+            ("size", None) => Placeholder::Size,
+            ("size", Some("h")) => Placeholder::SizeHuman,
+            ("mtime", None) => Placeholder::Mtime(DEFAULT_TIME_FORMAT.to_string()),
+            ("mtime", Some(format)) => {
+                // Catch bad formats now, rather than when the first file gets printed
+                if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                    return Err(format!("Invalid time format '{}' in {{{}}}", format, spec));
+                }
+
+                Placeholder::Mtime(format.to_string())
+            },
+            ("mode", None) => Placeholder::Mode,
+            ("depth", None) => Placeholder::Depth,
+            ("score", None) => Placeholder::Score,
+            _ => return Err(format!("Unknown placeholder {{{}}}", spec)),
+        };
+
+        Ok(placeholder)
+    }
+
+    fn render(&self, file_info: &FileInfo, out: &mut String) {
+        let path = &file_info.path;
+
+        let lossy = |part: Option<&std::ffi::OsStr>| {
+            part.map(|part| part.to_string_lossy().into_owned()).unwrap_or_default()
+        };
+
+        match self {
+            Placeholder::Path => out.push_str(&path.to_string_lossy()),
+            Placeholder::Name => out.push_str(&lossy(path.file_name())),
+            Placeholder::Stem => out.push_str(&lossy(path.file_stem())),
+            Placeholder::Ext => out.push_str(&lossy(path.extension())),
+            Placeholder::Parent => {
+                out.push_str(&path.parent().map(|parent| parent.to_string_lossy()).unwrap_or_default())
+            },
+            Placeholder::Size => out.push_str(&file_info.meta.len().to_string()),
+            Placeholder::SizeHuman => out.push_str(&pretty_fs_size(&(file_info.meta.len() as usize))),
+            Placeholder::Mtime(format) => {
+                if let Ok(modified) = file_info.meta.modified() {
+                    out.push_str(&format_time(modified, format));
+                }
+            },
+            Placeholder::Mode => out.push_str(&mode_string(file_info)),
+            Placeholder::Depth => out.push_str(&file_info.depth.to_string()),
+            Placeholder::Score => {
+                if let Some(score) = file_info.score {
+                    out.push_str(&score.to_string());
+                }
+            },
+        }
+    }
+}
+
+fn format_time(time: SystemTime, format: &str) -> String {
+    DateTime::<Local>::from(time).format(format).to_string()
+}
+
+#[cfg(unix)]
+fn mode_string(file_info: &FileInfo) -> String {
+    format!("{:o}", file_info.meta.mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn mode_string(file_info: &FileInfo) -> String {
+    match file_info.meta.permissions().readonly() {
+        true => "r".to_string(),
+        false => "rw".to_string(),
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Placeholder),
+}
+
+
+/// A `--format` string, parsed once up front, then rendered for each match. Placeholders go
+/// in braces (ex. `{name}`), `{{` and `}}` are literal braces, and `\t`, `\n`, `\0`, and `\\`
+/// are escapes.
+#[derive(Debug, Clone)]
+pub struct Template {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut segments = vec![];
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(chr) = chars.next() {
+            match chr {
+                '\\' => {
+                    let escaped = match chars.next() {
+                        Some('t') => '\t',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('0') => '\0',
+                        Some('\\') => '\\',
+                        Some(other) => return Err(format!("Unknown escape '\\{}' in format", other)),
+                        None => return Err("Format ends with a lone '\\'".to_string()),
+                    };
+
+                    literal.push(escaped);
+                },
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                },
+                '{' => {
+                    let mut spec = String::new();
+                    let mut closed = false;
+
+                    for chr in chars.by_ref() {
+                        if chr == '}' {
+                            closed = true;
+                            break;
+                        }
+
+                        spec.push(chr);
+                    }
+
+                    if !closed {
+                        return Err(format!("Unclosed placeholder '{{{}' in format", spec));
+                    }
+
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+
+                    segments.push(Segment::Field(Placeholder::parse(spec.trim())?));
+                },
+                '}' => return Err("Unmatched '}' in format. Use '}}' for a literal brace".to_string()),
+                other => literal.push(other),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Self {source: source.to_string(), segments})
+    }
+
+    pub fn render(&self, file_info: &FileInfo) -> String {
+        let mut out = String::new();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => out.push_str(literal),
+                Segment::Field(placeholder) => placeholder.render(file_info, &mut out),
+            }
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn literal(text: &str) -> Segment {
+        Segment::Literal(text.to_string())
+    }
+
+    #[test]
+    fn escapes_and_braces() {
+        let template = Template::parse("{name}\\t{{size}}\\n\\\\\\0").unwrap();
+        assert_eq!(template.segments, vec![Segment::Field(Placeholder::Name), literal("\t{size}\n\\\0")]);
+
+        let template = Template::parse("{ size:h } {mtime}").unwrap();
+        assert_eq!(template.segments, vec![
+            Segment::Field(Placeholder::SizeHuman),
+            literal(" "),
+            Segment::Field(Placeholder::Mtime(DEFAULT_TIME_FORMAT.to_string())),
+        ]);
+
+        assert!(Template::parse("\\q").is_err());
+        assert!(Template::parse("trailing \\").is_err());
+        assert!(Template::parse("{name").is_err());
+        assert!(Template::parse("name}").is_err());
+    }
+
+    #[test]
+    fn unknown_placeholders() {
+        assert_eq!(Template::parse("{nmae}").unwrap_err(), "Unknown placeholder {nmae}");
+        assert!(Template::parse("{size:k}").is_err());
+        assert!(Template::parse("{path:x}").is_err());
+        assert!(Template::parse("{}").is_err());
+    }
+
+    #[test]
+    fn time_formats() {
+        let template = Template::parse("{mtime:%Y/%m/%d}").unwrap();
+        assert_eq!(template.segments, vec![Segment::Field(Placeholder::Mtime("%Y/%m/%d".to_string()))]);
+
+        assert!(Template::parse("{mtime:%Y-%!}").is_err());
+    }
+}
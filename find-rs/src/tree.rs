@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::path::{Component, PathBuf};
+
+use finder::FileInfo;
+
+use utils::pretty_fs_size;
+
+use crate::colors::{self, LsColors};
+
+
+#[derive(Debug, Default)]
+struct TreeNode {
+    // Sorted by name, so the output doesn't depend on which worker got there first
+    children: BTreeMap<OsString, TreeNode>,
+    // False for directories that are only here because something under them matched
+    matched: bool,
+    broken_link: bool,
+    is_dir: bool,
+    // The colour of a matched entry. Anything else is a directory.
+    style: Option<String>,
+    // Rolled up from every matched file at or below this node
+    size: u64,
+    n_files: usize,
+}
+
+
+/// Collects matches into a tree of the directories that hold them, then prints it like
+/// `tree`. Results come in whatever order the workers find them, so nothing gets printed
+/// until everything has been added.
+pub struct Tree {
+    root: PathBuf,
+    node: TreeNode,
+    show_totals: bool,
+    colors: Option<LsColors>,
+}
+
+impl Tree {
+    pub fn new(root: PathBuf) -> Self {
+        Self {root, node: TreeNode::default(), show_totals: false, colors: None}
+    }
+
+    /// Shows the size of each file, and rolls sizes and file counts up into every directory.
+    pub fn with_totals(mut self, show_totals: bool) -> Self {
+        self.show_totals = show_totals;
+        self
+    }
+
+    /// Colours each name by its file type.
+    pub fn with_colors(mut self, colors: Option<LsColors>) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    pub fn insert(&mut self, file_info: &FileInfo) {
+        let rel_path = match file_info.path.strip_prefix(&self.root) {
+            Ok(rel_path) => rel_path,
+            Err(_) => file_info.path.as_path(),
+        };
+
+        // Directories are only there to hold their children, they don't add to the totals
+        let counts = !file_info.broken_link && !file_info.meta.is_dir();
+        let size = if counts {file_info.meta.len()} else {0};
+
+        let mut node = &mut self.node;
+        add_totals(node, size, counts);
+
+        for component in rel_path.components() {
+            let name = match component {
+                Component::Normal(name) => name.to_os_string(),
+                Component::RootDir => OsString::from("/"),
+                _ => continue,
+            };
+
+            node = node.children.entry(name).or_default();
+            add_totals(node, size, counts);
+        }
+
+        node.matched = true;
+        node.broken_link = file_info.broken_link;
+        node.is_dir = file_info.meta.is_dir();
+        node.style = self.colors.as_ref()
+            .and_then(|colors| colors.style(file_info))
+            .map(String::from);
+    }
+
+    pub fn render<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "{}", self.root.display())?;
+        self.write_totals(out, &self.node)?;
+        writeln!(out)?;
+
+        self.render_children(out, &self.node, &mut String::new())
+    }
+
+    fn render_children<W: Write>(&self, out: &mut W, node: &TreeNode, prefix: &mut String) -> io::Result<()> {
+        let n_children = node.children.len();
+
+        for (idx, (name, child)) in node.children.iter().enumerate() {
+            let is_last = idx + 1 == n_children;
+
+            let branch = if is_last {"└── "} else {"├── "};
+            let style = match child.matched {
+                true => child.style.as_deref(),
+                false => self.colors.as_ref().and_then(LsColors::dir_style),
+            };
+            write!(out, "{}{}{}", prefix, branch, colors::paint(&name.to_string_lossy(), style))?;
+
+            if child.broken_link {
+                write!(out, " (broken link)")?;
+            }
+
+            self.write_totals(out, child)?;
+            writeln!(out)?;
+
+            if !child.children.is_empty() {
+                let prefix_len = prefix.len();
+                prefix.push_str(if is_last {"    "} else {"│   "});
+
+                self.render_children(out, child, prefix)?;
+                prefix.truncate(prefix_len);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_totals<W: Write>(&self, out: &mut W, node: &TreeNode) -> io::Result<()> {
+        if !self.show_totals || node.broken_link {
+            return Ok(());
+        }
+
+        let size = pretty_fs_size(&(node.size as usize));
+
+        // Directories always get a count, even a matched one that's empty
+        match node.matched && !node.is_dir {
+            true => write!(out, " [{}]", size),
+            false => {
+                let files = if node.n_files == 1 {"file"} else {"files"};
+                write!(out, " [{} in {} {}]", size, node.n_files, files)
+            },
+        }
+    }
+}
+
+fn add_totals(node: &mut TreeNode, size: u64, counts: bool) {
+    if counts {
+        node.size += size;
+        node.n_files += 1;
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+
+    fn render(tree: &Tree) -> String {
+        let mut out = Vec::new();
+        tree.render(&mut out).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn rendering() {
+        let dir_meta = fs::metadata(env::temp_dir()).unwrap();
+        let file_meta = fs::metadata(env::current_exe().unwrap()).unwrap();
+
+        let insert_all = |tree: &mut Tree| {
+            tree.insert(&FileInfo::new("/root/b/two.rs".into(), file_meta.clone(), 2));
+            tree.insert(&FileInfo::new("/root/a.rs".into(), file_meta.clone(), 1));
+            tree.insert(&FileInfo::new("/root/empty".into(), dir_meta.clone(), 1));
+        };
+
+        let mut plain = Tree::new("/root".into());
+        insert_all(&mut plain);
+
+        assert_eq!(render(&plain), "/root\n├── a.rs\n├── b\n│   └── two.rs\n└── empty\n");
+
+        let mut totals = Tree::new("/root".into()).with_totals(true);
+        insert_all(&mut totals);
+
+        let rendered = render(&totals);
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].ends_with(" in 2 files]"));
+        assert!(lines[1].starts_with("├── a.rs [") && !lines[1].contains(" in "));
+        assert!(lines[2].ends_with(" in 1 file]"));
+        assert!(lines[4].starts_with("└── empty [") && lines[4].ends_with(" in 0 files]"));
+    }
+}
@@ -1,18 +1,67 @@
-use std::path::PathBuf;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::ffi::OsStr;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
 
 use thread_pool::ThreadPool;
 
 use super::Finder;
-use super::pattern::{FileType, Pattern};
+use super::exclude::Exclude;
+use super::filesystem::{self, FsKind};
+use super::pattern::{FileType, Pattern, PatternCost, PermMask};
+use super::sniff::{self, TypeDetection};
+use super::walk::FileInfo;
 
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindOnly {
+    Files,
+    Directories,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderBy {
+    Size,
+    Filename,
+    // How well a fuzzy pattern matched. Anything without a score comes first.
+    Score,
+}
+
+impl OrderBy {
+    pub fn compare(&self, a: &FileInfo, b: &FileInfo) -> Ordering {
+        match self {
+            OrderBy::Size => a.meta.len().cmp(&b.meta.len()),
+            OrderBy::Filename => a.path.file_name().cmp(&b.path.file_name()),
+            OrderBy::Score => a.score.cmp(&b.score),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Default)]
 pub struct FinderConfig {
     pub root: Option<PathBuf>,
     pub patterns: Vec<Pattern>,
+    pub excludes: Vec<Exclude>,
     pub file_types: Vec<FileType>,
+    pub type_detection: TypeDetection,
+    pub find_only: Option<FindOnly>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub order_by: Option<OrderBy>,
+    pub desc_order: bool,
+    pub limit: Option<usize>,
+    pub use_ignore_files: bool,
+    pub follow_symlinks: bool,
+    pub report_broken_links: bool,
+    pub min_depth: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub one_file_system: bool,
+    pub search_pseudo_fs: bool,
+    pub skip_network_fs: bool,
 
-    pool_size: Option<usize>,
+    pub(crate) pool_size: Option<usize>,
 }
 
 impl FinderConfig {
@@ -20,65 +69,424 @@ impl FinderConfig {
         Self::default()
     }
 
-    pub fn with_pool_size(&mut self, size: usize) -> Self {
+    pub fn with_pool_size(mut self, size: usize) -> Self {
         self.pool_size = Some(size);
-        *self
+        self
     }
 
-    pub fn with_pattern(&mut self, pattern: Pattern) -> Self {
+    pub fn with_pattern(mut self, pattern: Pattern) -> Self {
         self.patterns.push(pattern);
-        *self
+        self
     }
 
-    pub fn with_patterns<V>(&mut self, patterns: V) -> Self
+    pub fn with_patterns<V>(mut self, patterns: V) -> Self
     where
         V: Into<Vec<Pattern>>
     {
         self.patterns.extend(patterns.into());
-        *self
+        self
     }
 
-    pub fn file_type(&mut self, file_type: FileType) -> Self {
+    /// Leaves anything matching the exclude out of the search, without reading excluded
+    /// directories at all.
+    pub fn with_exclude(mut self, exclude: Exclude) -> Self {
+        self.excludes.push(exclude);
+        self
+    }
+
+    pub fn with_excludes<V>(mut self, excludes: V) -> Self
+    where
+        V: Into<Vec<Exclude>>
+    {
+        self.excludes.extend(excludes.into());
+        self
+    }
+
+    pub fn file_type(mut self, file_type: FileType) -> Self {
         self.file_types.push(file_type);
-        *self
+        self
     }
 
-    pub fn file_types<V>(&mut self, file_types: V) -> Self
+    pub fn file_types<V>(mut self, file_types: V) -> Self
     where
         V: Into<Vec<FileType>>
     {
         self.file_types.extend(file_types.into());
-        *self
+        self
+    }
+
+    /// How `file_types` are detected. Sniffing reads the start of each file, so it's slower,
+    /// but it catches files without (or with the wrong) extension.
+    pub fn with_type_detection(mut self, type_detection: TypeDetection) -> Self {
+        self.type_detection = type_detection;
+        self
+    }
+
+    pub fn with_find_only(mut self, find_only: FindOnly) -> Self {
+        self.find_only = Some(find_only);
+        self
+    }
+
+    pub fn with_min_size(mut self, min_size: u64) -> Self {
+        self.min_size = Some(min_size);
+        self
+    }
+
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Instead of handing back results as they're found, collect them all and sort.
+    pub fn with_order_by(mut self, order_by: OrderBy, desc_order: bool) -> Self {
+        self.order_by = Some(order_by);
+        self.desc_order = desc_order;
+        self
+    }
+
+    /// Only return this many results. With an `order_by` these are the first by that order,
+    /// otherwise whichever are found first.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Skip anything matched by .gitignore, .ignore, .findignore, or .git/info/exclude files.
+    pub fn with_ignore_files(mut self, use_ignore_files: bool) -> Self {
+        self.use_ignore_files = use_ignore_files;
+        self
+    }
+
+    /// Treat symlinks as whatever they point to, descending into linked directories.
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// When following symlinks, return links to missing targets as results, flagged with
+    /// `FileInfo::broken_link`. Otherwise they're skipped.
+    pub fn with_broken_links(mut self, report_broken_links: bool) -> Self {
+        self.report_broken_links = report_broken_links;
+        self
+    }
+
+    /// Only match entries at least this many levels below the root. Entries directly in
+    /// the root are at depth 1.
+    pub fn with_min_depth(mut self, min_depth: usize) -> Self {
+        self.min_depth = Some(min_depth);
+        self
+    }
+
+    /// Don't match, or even read, anything more than this many levels below the root.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Don't descend into directories on a different filesystem than the root, like `find -xdev`.
+    /// Mount points themselves can still match.
+    pub fn with_one_file_system(mut self, one_file_system: bool) -> Self {
+        self.one_file_system = one_file_system;
+        self
+    }
+
+    /// Also descend into pseudo filesystems like /proc and /sys, which are skipped by default.
+    /// A root that's on one is always searched.
+    pub fn with_pseudo_fs(mut self, search_pseudo_fs: bool) -> Self {
+        self.search_pseudo_fs = search_pseudo_fs;
+        self
+    }
+
+    /// Don't descend into network filesystems like nfs, cifs and sshfs.
+    pub fn with_skip_network_fs(mut self, skip_network_fs: bool) -> Self {
+        self.skip_network_fs = skip_network_fs;
+        self
+    }
+
+    /// Only match entries owned by this uid. See `Pattern::user` to look one up by name.
+    pub fn with_uid(self, uid: u32) -> Self {
+        self.with_pattern(Pattern::uid(uid))
+    }
+
+    /// Only match entries owned by this gid. See `Pattern::group` to look one up by name.
+    pub fn with_gid(self, gid: u32) -> Self {
+        self.with_pattern(Pattern::gid(gid))
+    }
+
+    /// Only match entries whose owner isn't in /etc/passwd.
+    pub fn with_no_user(self) -> Self {
+        self.with_pattern(Pattern::no_user())
+    }
+
+    /// Only match entries whose group isn't in /etc/group.
+    pub fn with_no_group(self) -> Self {
+        self.with_pattern(Pattern::no_group())
     }
 
-    pub fn clear_patterns(&mut self) -> Self {
+    /// Only match entries whose permission bits pass the mask.
+    pub fn with_perm(self, mask: PermMask) -> Self {
+        self.with_pattern(Pattern::perm(mask))
+    }
+
+    pub fn clear_patterns(mut self) -> Self {
         self.patterns.clear();
-        *self
+        self
     }
 
-    pub fn clear_file_types(&mut self) -> Self {
+    pub fn clear_file_types(mut self) -> Self {
         self.file_types.clear();
-        *self
+        self
     }
 
     pub fn build(self) -> Finder {
         self.into()
     }
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.excludes.iter().any(|exclude| exclude.is_match(path))
+    }
+
+    /// Whether `path` is left out before it's even stat'd. That's anything excluded, plus
+    /// git's own `.git` directory when ignore files are honoured, same as git itself.
+    pub(crate) fn is_skipped(&self, path: &Path) -> bool {
+        self.is_excluded(path) || (self.use_ignore_files && path.file_name() == Some(OsStr::new(".git")))
+    }
+
+    /// Whether the walk should go into `dir`, found in a directory on `parent_dev`. The
+    /// filesystem type is only looked up at mount points, so this is free everywhere else.
+    pub(crate) fn can_enter_dir(&self, dir: &Path, meta: &Metadata, parent_dev: u64) -> bool {
+        let dev = filesystem::dev(meta);
+
+        if dev == parent_dev {
+            return true;
+        }
+
+        if self.one_file_system {
+            return false;
+        }
+
+        match filesystem::fs_kind(dir, dev) {
+            FsKind::Pseudo => self.search_pseudo_fs,
+            FsKind::Network => !self.skip_network_fs,
+            FsKind::Local => true,
+        }
+    }
+
+    /// Checks a single file or directory against everything in the config. Top level
+    /// patterns are combined like so:
+    ///
+    /// - Name patterns (ext, regex, fuzzy, file type) match if any one of them does.
+    /// - Metadata filters (timestamps, owners, permissions) and combinators (And, Or, Not)
+    ///   all have to match.
+    /// - Content searches match if any one of them does.
+    ///
+    /// and all three groups have to pass, so `-e rs --grep foo` means rust files containing
+    /// foo, not either of the two. Cheaper checks run first, so files are only read once
+    /// everything else has passed.
+    pub fn is_match(&self, file_info: &mut FileInfo) -> bool {
+        let is_dir = file_info.meta.is_dir();
+
+        if self.min_depth.map(|min_depth| file_info.depth < min_depth).unwrap_or(false)
+            || self.max_depth.map(|max_depth| file_info.depth > max_depth).unwrap_or(false) {
+            return false;
+        }
+
+        match self.find_only {
+            Some(FindOnly::Files) if !file_info.meta.is_file() => return false,
+            Some(FindOnly::Directories) if !is_dir => return false,
+            _ => (),
+        }
+
+        // Size limits only make sense for files, so directories never pass them.
+        if self.min_size.is_some() || self.max_size.is_some() {
+            if is_dir {
+                return false;
+            }
+
+            let size = file_info.meta.len();
+
+            if self.min_size.map(|min_size| size < min_size).unwrap_or(false)
+                || self.max_size.map(|max_size| size > max_size).unwrap_or(false) {
+                return false;
+            }
+        }
+
+        if !self.matches_names(file_info) {
+            return false;
+        }
+
+        for cost in &[PatternCost::Metadata, PatternCost::Content] {
+            let passed = self.patterns.iter()
+                .filter(|pattern| is_filter(pattern) && pattern.cost() == *cost)
+                .all(|pattern| pattern.matches_file(file_info));
+
+            if !passed {
+                return false;
+            }
+        }
+
+        // Left until after the other checks, since sniffing has to read the file
+        if !self.file_types.is_empty() && !self.matches_file_types(file_info) {
+            return false;
+        }
+
+        let mut contents = self.patterns.iter()
+            .filter(|pattern| !is_filter(pattern) && pattern.cost() == PatternCost::Content)
+            .peekable();
+
+        contents.peek().is_none() || contents.any(|pattern| pattern.matches_file(file_info))
+    }
+
+    /// Checks a broken link. There's nothing to look at but the link itself, so only the
+    /// depth limits and anything that goes by the name apply.
+    pub fn is_broken_link_match(&self, file_info: &mut FileInfo) -> bool {
+        self.min_depth.map(|min_depth| file_info.depth >= min_depth).unwrap_or(true)
+            && self.max_depth.map(|max_depth| file_info.depth <= max_depth).unwrap_or(true)
+            && self.matches_names(file_info)
+    }
+
+    // Any one of the name patterns, and every filter made up of nothing but names
+    fn matches_names(&self, file_info: &mut FileInfo) -> bool {
+        let mut names = self.patterns.iter()
+            .filter(|pattern| !is_filter(pattern) && pattern.cost() == PatternCost::Name)
+            .peekable();
+
+        if names.peek().is_some() && !names.any(|pattern| pattern.matches_file(file_info)) {
+            return false;
+        }
+
+        self.patterns.iter()
+            .filter(|pattern| is_filter(pattern) && pattern.cost() == PatternCost::Name)
+            .all(|pattern| pattern.matches_file(file_info))
+    }
+
+    fn matches_file_types(&self, file_info: &FileInfo) -> bool {
+        let path = file_info.path.as_path();
+
+        if self.type_detection != TypeDetection::Sniff
+            && self.file_types.iter().any(|f_type| f_type.matches_file(path)) {
+            return true;
+        }
+
+        if self.type_detection == TypeDetection::Extension || !file_info.meta.is_file() {
+            return false;
+        }
+
+        match sniff::sniff_file(path) {
+            Some(mime) => self.file_types.iter().any(|f_type| f_type.matches_sniffed(path, &mime)),
+            None => false,
+        }
+    }
+
+    /// Sorts collected results by `order_by`, if one was set.
+    pub fn sort(&self, results: &mut [FileInfo]) {
+        if let Some(order_by) = self.order_by {
+            match self.desc_order {
+                true => results.sort_by(|a, b| order_by.compare(b, a)),
+                false => results.sort_by(|a, b| order_by.compare(a, b)),
+            }
+        }
+    }
+
+    /// Sorts the results and cuts them down to the `limit`, if either were set. With both,
+    /// only `limit` results are held onto at a time, however many there are in total.
+    pub fn arrange<I>(&self, results: I) -> Vec<FileInfo>
+    where
+        I: IntoIterator<Item = FileInfo>
+    {
+        match (self.order_by, self.limit) {
+            (Some(_), Some(limit)) => self.top_n(results, limit),
+            (None, Some(limit)) => results.into_iter().take(limit).collect(),
+            (_, None) => {
+                let mut results: Vec<FileInfo> = results.into_iter().collect();
+                self.sort(&mut results);
+                results
+            },
+        }
+    }
+
+    // Keeps the first n results in a heap with the last of them on top, so each new result
+    // only has to beat that one to get in
+    fn top_n<I>(&self, results: I, n: usize) -> Vec<FileInfo>
+    where
+        I: IntoIterator<Item = FileInfo>
+    {
+        if n == 0 {
+            return vec![];
+        }
+
+        let mut heap: BinaryHeap<Ordered> = BinaryHeap::with_capacity(n + 1);
+
+        for file_info in results {
+            heap.push(Ordered {file_info, config: self});
+
+            if heap.len() > n {
+                heap.pop();
+            }
+        }
+
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|ordered| ordered.file_info)
+            .collect()
+    }
+
+    fn compare(&self, a: &FileInfo, b: &FileInfo) -> Ordering {
+        let ordering = match (self.order_by, self.desc_order) {
+            (Some(order_by), true) => order_by.compare(b, a),
+            (Some(order_by), false) => order_by.compare(a, b),
+            (None, _) => Ordering::Equal,
+        };
+
+        // So ties don't depend on which worker got there first
+        ordering.then_with(|| a.path.cmp(&b.path))
+    }
+}
+
+
+// Filters all have to match, where only one of the other patterns in each group does
+fn is_filter(pattern: &Pattern) -> bool {
+    pattern.is_metadata_filter() || pattern.is_combinator()
+}
+
+// A result in a top n heap, ordered by the config's order_by
+struct Ordered<'a> {
+    file_info: FileInfo,
+    config: &'a FinderConfig,
+}
+
+impl Ord for Ordered<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.config.compare(&self.file_info, &other.file_info)
+    }
+}
+
+impl PartialOrd for Ordered<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
+impl PartialEq for Ordered<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ordered<'_> {}
+
 impl<I: Into<PathBuf>> From<I> for FinderConfig {
     fn from(path: I) -> Self {
         Self {root: Some(path.into()), ..Default::default()}
     }
 }
 
-impl Into<Finder> for FinderConfig {
-    fn into(self) -> Finder {
+impl From<FinderConfig> for Finder {
+    fn from(config: FinderConfig) -> Self {
         Finder {
-            root: self.root.unwrap_or_else(|| PathBuf::from("/")),
-            config: self,
-            pool: Some(ThreadPool::create_pool(self.pool_size)),
-            ..Default::default()
+            root: config.root.clone().unwrap_or_else(|| PathBuf::from("/")),
+            pool: Some(ThreadPool::create_pool(config.pool_size)),
+            config,
         }
     }
 }
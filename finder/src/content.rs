@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use regex::Regex;
+
+
+// Only this much of a line gets searched. Without a cap, a big file without newlines (like
+// minified JS) would be read into memory in one go.
+static MAX_LINE_LEN: usize = 64 * 1024;
+
+
+/// A single line from a file that matched a content search, or one of the lines of context
+/// around a match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchedLine {
+    // Starts from 1, like grep
+    pub line_number: usize,
+    pub text: String,
+    pub is_context: bool,
+}
+
+
+/// Searches the contents of files for a regex, line by line.
+#[derive(Debug, Clone)]
+pub struct ContentSearch {
+    pub regex: Regex,
+    // Files bigger than this are skipped entirely
+    pub max_file_size: Option<u64>,
+    // Number of lines to keep before and after each match
+    pub context_lines: usize,
+}
+
+impl ContentSearch {
+    pub fn new(regex: Regex) -> Self {
+        Self {regex, max_file_size: None, context_lines: 0}
+    }
+
+    pub fn with_max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = Some(max_file_size);
+        self
+    }
+
+    pub fn with_context_lines(mut self, context_lines: usize) -> Self {
+        self.context_lines = context_lines;
+        self
+    }
+
+    /// Returns the matching lines (plus context), or None if nothing matched, the file
+    /// couldn't be read, was too big, or looks like a binary file. Only the first 64 KiB of
+    /// each line is searched.
+    pub fn search_file(&self, path: &Path) -> Option<Vec<MatchedLine>> {
+        let file = File::open(path).ok()?;
+
+        if let Some(max_file_size) = self.max_file_size {
+            if file.metadata().ok()?.len() > max_file_size {
+                return None;
+            }
+        }
+
+        let mut reader = BufReader::new(file);
+
+        // Text files don't have NUL bytes, so assume anything with one in the first block is
+        // binary, before scanning any of it
+        match reader.fill_buf() {
+            Ok(block) if block.contains(&0) => return None,
+            Ok(_) => (),
+            Err(_) => return None,
+        }
+
+        let mut buf: Vec<u8> = Vec::new();
+
+        let mut found: Vec<MatchedLine> = Vec::new();
+        let mut before: VecDeque<MatchedLine> = VecDeque::with_capacity(self.context_lines);
+        let mut after_remaining: usize = 0;
+
+        let mut line_number = 0;
+        loop {
+            buf.clear();
+
+            match (&mut reader).take(MAX_LINE_LEN as u64).read_until(b'\n', &mut buf) {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(_) => return None,
+            }
+
+            if buf.len() == MAX_LINE_LEN && buf.last() != Some(&b'\n') && skip_line(&mut reader).is_err() {
+                return None;
+            }
+
+            // The first block can look like text, with binary data further in
+            if buf.contains(&0) {
+                return None;
+            }
+
+            line_number += 1;
+
+            let text = String::from_utf8_lossy(&buf)
+                .trim_end_matches(&['\n', '\r'][..])
+                .to_string();
+
+            if self.regex.is_match(&text) {
+                found.extend(before.drain(..));
+                found.push(MatchedLine {line_number, text, is_context: false});
+                after_remaining = self.context_lines;
+            }
+            else if after_remaining > 0 {
+                found.push(MatchedLine {line_number, text, is_context: true});
+                after_remaining -= 1;
+            }
+            else if self.context_lines > 0 {
+                if before.len() == self.context_lines {
+                    before.pop_front();
+                }
+                before.push_back(MatchedLine {line_number, text, is_context: true});
+            }
+        }
+
+        match found.is_empty() {
+            true => None,
+            false => Some(found),
+        }
+    }
+}
+
+// Throws away the rest of the current line, without keeping any of it around
+fn skip_line<R: BufRead>(reader: &mut R) -> io::Result<()> {
+    loop {
+        let block = reader.fill_buf()?;
+
+        if block.is_empty() {
+            return Ok(());
+        }
+
+        match block.iter().position(|&byte| byte == b'\n') {
+            Some(idx) => {
+                reader.consume(idx + 1);
+                return Ok(());
+            },
+            None => {
+                let len = block.len();
+                reader.consume(len);
+            },
+        }
+    }
+}
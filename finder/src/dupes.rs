@@ -0,0 +1,225 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use thread_pool::{ThreadPool, TaskResult};
+
+use xxhash_rust::xxh3::{xxh3_128, Xxh3};
+
+use super::walk::FileInfo;
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+
+// How much of each end of a file the partial hash reads
+static PARTIAL_HASH_LEN: u64 = 4 * 1024;
+
+static READ_BUF_LEN: usize = 64 * 1024;
+
+
+/// A set of files with identical contents.
+#[derive(Debug, Clone)]
+pub struct DupeGroup {
+    // The size of each file
+    pub size: u64,
+    pub files: Vec<FileInfo>,
+}
+
+impl DupeGroup {
+    /// Space that could be freed by keeping only one of the files.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.files.len() as u64).saturating_sub(1)
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+enum HashKind {
+    // The first and last few KiB, to cheaply rule out files that just happen to be the same size
+    Partial,
+    Full,
+}
+
+
+/// Finds groups of duplicate files. Candidates are grouped by size, then by a hash of
+/// the first and last 4 KiB, and only files that still look the same get fully hashed.
+#[derive(Debug, Clone)]
+pub struct DupeFinder {
+    pub min_size: u64,
+    // When true, hard links to the same file count as duplicates of each other
+    pub hard_links_are_dupes: bool,
+    pool_size: Option<usize>,
+}
+
+impl Default for DupeFinder {
+    fn default() -> Self {
+        // Empty files are all "identical", which isn't useful to know
+        Self {min_size: 1, hard_links_are_dupes: false, pool_size: None}
+    }
+}
+
+impl DupeFinder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_min_size(mut self, min_size: u64) -> Self {
+        self.min_size = min_size.max(1);
+        self
+    }
+
+    /// Hard links share their data, so deleting one doesn't free anything, and by default
+    /// they're treated as a single file. Pass true to report them as duplicates of each other.
+    pub fn with_hard_links(mut self, hard_links_are_dupes: bool) -> Self {
+        self.hard_links_are_dupes = hard_links_are_dupes;
+        self
+    }
+
+    pub fn with_pool_size(mut self, pool_size: usize) -> Self {
+        self.pool_size = Some(pool_size);
+        self
+    }
+
+    /// Returns every group of duplicates among the files, biggest waste of space first.
+    /// Anything that isn't a regular file is skipped.
+    pub fn find<I>(&self, files: I) -> Vec<DupeGroup>
+    where
+        I: IntoIterator<Item = FileInfo>
+    {
+        let mut seen_inodes = HashSet::new();
+
+        let files: Vec<FileInfo> = files.into_iter()
+            .filter(|file_info| file_info.meta.is_file() && file_info.meta.len() >= self.min_size)
+            .filter(|file_info| self.hard_links_are_dupes || seen_inodes.insert(inode_id(file_info)))
+            .collect();
+
+        // Only files that share a size can possibly be the same
+        let by_size = group_by(0..files.len(), |idx| Some(files[*idx].meta.len()));
+
+        // Files small enough for the partial hash to cover all of them are done after it
+        let (small, large): (Vec<Vec<usize>>, Vec<Vec<usize>>) = self.split_by_hash(&files, by_size, HashKind::Partial)
+            .into_iter()
+            .partition(|group| files[group[0]].meta.len() <= PARTIAL_HASH_LEN * 2);
+
+        let mut groups: Vec<Vec<usize>> = small;
+        groups.extend(self.split_by_hash(&files, large, HashKind::Full));
+
+        let mut files: Vec<Option<FileInfo>> = files.into_iter().map(Some).collect();
+
+        let mut dupes: Vec<DupeGroup> = groups.into_iter()
+            .map(|group| {
+                let mut group_files: Vec<FileInfo> = group.into_iter()
+                    .filter_map(|idx| files[idx].take())
+                    .collect();
+
+                group_files.sort_by(|a, b| a.path.cmp(&b.path));
+
+                DupeGroup {size: group_files[0].meta.len(), files: group_files}
+            })
+            .collect();
+
+        dupes.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.files[0].path.cmp(&b.files[0].path)));
+        dupes
+    }
+
+    /// Hashes every file in the groups across the thread pool, and splits each group up by
+    /// hash. Groups (and files that couldn't be read) with nothing left to compare against
+    /// are dropped.
+    fn split_by_hash(&self, files: &[FileInfo], groups: Vec<Vec<usize>>, kind: HashKind) -> Vec<Vec<usize>> {
+        let mut pool: ThreadPool<(usize, Option<u128>)> = ThreadPool::create_pool(self.pool_size);
+
+        let mut n_jobs = 0;
+        for idx in groups.iter().flatten().copied() {
+            let path = files[idx].path.clone();
+            let size = files[idx].meta.len();
+
+            let spawned = pool.spawn(move || {
+                TaskResult::from_result((idx, hash_file(&path, size, kind).ok())).to_opt()
+            });
+
+            if spawned.is_ok() {
+                n_jobs += 1;
+            }
+        }
+
+        let mut hashes: HashMap<usize, u128> = HashMap::with_capacity(n_jobs);
+        while let Some((idx, hash)) = pool.recv_result() {
+            if let Some(hash) = hash {
+                hashes.insert(idx, hash);
+            }
+        }
+
+        pool.join();
+
+        groups.into_iter()
+            .flat_map(|group| group_by(group, |idx| hashes.get(idx).copied()))
+            .collect()
+    }
+}
+
+/// Splits the indexes up by key, keeping only the groups with more than one member.
+/// Anything without a key is dropped.
+fn group_by<I, K, F>(indexes: I, key: F) -> Vec<Vec<usize>>
+where
+    I: IntoIterator<Item = usize>,
+    K: std::hash::Hash + Eq,
+    F: Fn(&usize) -> Option<K>,
+{
+    let mut groups: HashMap<K, Vec<usize>> = HashMap::new();
+
+    for idx in indexes {
+        if let Some(group_key) = key(&idx) {
+            groups.entry(group_key).or_default().push(idx);
+        }
+    }
+
+    groups.into_values()
+        .filter(|group| group.len() > 1)
+        .collect()
+}
+
+#[cfg(unix)]
+fn inode_id(file_info: &FileInfo) -> (u64, u64) {
+    (file_info.meta.dev(), file_info.meta.ino())
+}
+
+// No inodes to go by, so every file is its own
+#[cfg(not(unix))]
+fn inode_id(file_info: &FileInfo) -> std::path::PathBuf {
+    file_info.path.clone()
+}
+
+fn hash_file(path: &Path, size: u64, kind: HashKind) -> io::Result<u128> {
+    let mut file = File::open(path)?;
+
+    match kind {
+        HashKind::Partial if size > PARTIAL_HASH_LEN * 2 => {
+            let mut buf = vec![0; (PARTIAL_HASH_LEN * 2) as usize];
+            let (head, tail) = buf.split_at_mut(PARTIAL_HASH_LEN as usize);
+
+            file.read_exact(head)?;
+            file.seek(SeekFrom::End(-(PARTIAL_HASH_LEN as i64)))?;
+            file.read_exact(tail)?;
+
+            Ok(xxh3_128(&buf))
+        },
+        // Small enough that the "partial" hash is the whole file anyway
+        HashKind::Partial | HashKind::Full => {
+            let mut hasher = Xxh3::new();
+            let mut buf = vec![0; READ_BUF_LEN];
+
+            loop {
+                match file.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n_read) => hasher.update(&buf[..n_read]),
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(err),
+                }
+            }
+
+            Ok(hasher.digest128())
+        },
+    }
+}
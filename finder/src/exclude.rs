@@ -0,0 +1,61 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::path::Path;
+
+use regex::Regex;
+
+use super::glob::Glob;
+
+
+/// Something to leave out of a search entirely. Unlike a pattern that doesn't match, an
+/// excluded directory is pruned: it's never read, so nothing below it costs anything.
+/// Only the path is looked at, so entries are excluded before they're even stat'd.
+#[derive(Debug, Clone)]
+pub enum Exclude {
+    // An exact file name, like "node_modules"
+    Name(OsString),
+    Glob(Glob),
+    // Matched against the full path
+    Regex(Regex),
+}
+
+impl Exclude {
+    pub fn name<T: Into<OsString>>(name: T) -> Self {
+        Exclude::Name(name.into())
+    }
+
+    /// Same as `Glob::new`, matched against the name unless the pattern has a '/' in it.
+    pub fn glob(pattern: &str) -> Result<Self, regex::Error> {
+        Glob::new(pattern).map(Exclude::Glob)
+    }
+
+    pub fn regex(regex: Regex) -> Self {
+        Exclude::Regex(regex)
+    }
+
+    /// A glob if the pattern has any glob characters in it, otherwise an exact name.
+    pub fn parse(pattern: &str) -> Result<Self, regex::Error> {
+        match pattern.contains(['*', '?', '[', '{', '/']) {
+            true => Self::glob(pattern),
+            false => Ok(Self::name(pattern)),
+        }
+    }
+
+    pub fn is_match(&self, path: &Path) -> bool {
+        match self {
+            Exclude::Name(name) => path.file_name() == Some(name.as_os_str()),
+            Exclude::Glob(glob) => glob.is_match(path),
+            Exclude::Regex(regex) => path.to_str().map(|path_str| regex.is_match(path_str)).unwrap_or(false),
+        }
+    }
+}
+
+impl fmt::Display for Exclude {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Exclude::Name(name) => write!(f, "{}", name.to_string_lossy()),
+            Exclude::Glob(glob) => write!(f, "{}", glob),
+            Exclude::Regex(regex) => write!(f, "{}", regex),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs::Metadata;
+use std::path::Path;
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+
+// statfs f_type magic numbers, from linux/magic.h
+#[cfg(target_os = "linux")]
+mod magic {
+    pub static PSEUDO: &[u32] = &[
+        0x0000_9fa0, // proc
+        0x6265_6572, // sysfs
+        0x0027_e0eb, // cgroup
+        0x6367_7270, // cgroup2
+        0x0000_1cd1, // devpts
+        0x6462_6720, // debugfs
+        0x7472_6163, // tracefs
+        0x7363_6673, // securityfs
+        0x6165_676c, // pstore
+        0xcafe_4a11, // bpf
+        0x6265_6570, // configfs
+        0x1980_0202, // mqueue
+        0x9584_58f6, // hugetlbfs
+        0x4249_4e4d, // binfmt_misc
+        0xde5e_81e4, // efivarfs
+        0x6573_5543, // fusectl
+        0xf97c_ff8c, // selinuxfs
+        0x6e73_6673, // nsfs
+    ];
+
+    pub static NETWORK: &[u32] = &[
+        0x0000_6969, // nfs
+        0x0000_517b, // smb
+        0xff53_4d42, // cifs
+        0xfe53_4d42, // smb2
+        0x7375_7245, // coda
+        0x5346_414f, // afs
+        0x6b41_4653, // kafs
+        0x00c3_6400, // ceph
+        0x0102_1997, // 9p
+    ];
+
+    // Both of these cover more than one kind of filesystem, so the mount table has to say which
+    pub static TMPFS: u32 = 0x0102_1994;
+    pub static FUSE: u32 = 0x6573_5546;
+}
+
+// Filesystem types from the mount table that statfs can't tell apart from the rest
+#[cfg(target_os = "linux")]
+static PSEUDO_MOUNT_TYPES: &[&str] = &["devtmpfs"];
+
+#[cfg(target_os = "linux")]
+static NETWORK_MOUNT_TYPES: &[&str] = &["fuse.sshfs", "fuse.rclone", "fuse.s3fs", "fuse.gcsfuse", "fuse.curlftpfs"];
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsKind {
+    // Anything that isn't one of the others, including filesystems we can't identify
+    Local,
+    // Made up by the kernel, like /proc and /sys. Huge, always changing, and some files
+    // block forever when read.
+    Pseudo,
+    // On another machine, so slow to walk
+    Network,
+}
+
+
+/// The device a file or directory is on. Every entry on the same filesystem shares one.
+#[cfg(unix)]
+pub fn dev(meta: &Metadata) -> u64 {
+    meta.dev()
+}
+
+#[cfg(not(unix))]
+pub fn dev(_meta: &Metadata) -> u64 {
+    0
+}
+
+/// The kind of filesystem `path` is on, where `dev` is its device. Only worth asking at a
+/// mount point, since everything else is on the same filesystem as its parent.
+#[cfg(target_os = "linux")]
+pub fn fs_kind(path: &Path, dev: u64) -> FsKind {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(c_path) => c_path,
+        Err(_) => return FsKind::Local,
+    };
+
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return FsKind::Local;
+    }
+
+    // f_type is signed on some targets, but the magic numbers are all 32 bits
+    let fs_magic = stat.f_type as u32;
+
+    if magic::PSEUDO.contains(&fs_magic) {
+        FsKind::Pseudo
+    }
+    else if magic::NETWORK.contains(&fs_magic) {
+        FsKind::Network
+    }
+    else if fs_magic == magic::TMPFS || fs_magic == magic::FUSE {
+        mount_type(dev).map(|fs_type| kind_of_mount(&fs_type)).unwrap_or(FsKind::Local)
+    }
+    else {
+        FsKind::Local
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn fs_kind(_path: &Path, _dev: u64) -> FsKind {
+    FsKind::Local
+}
+
+#[cfg(target_os = "linux")]
+fn kind_of_mount(fs_type: &str) -> FsKind {
+    if PSEUDO_MOUNT_TYPES.contains(&fs_type) {
+        FsKind::Pseudo
+    }
+    else if NETWORK_MOUNT_TYPES.contains(&fs_type) {
+        FsKind::Network
+    }
+    else {
+        FsKind::Local
+    }
+}
+
+// Looks up the type of the filesystem mounted on `dev` in /proc/self/mountinfo, where each
+// line is "id parent major:minor root mount_point options [optional fields] - type source ..."
+#[cfg(target_os = "linux")]
+fn mount_type(dev: u64) -> Option<String> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+
+    mountinfo.lines().find_map(|line| {
+        let (mount, fs) = line.split_once(" - ")?;
+        let (major, minor) = mount.split_whitespace().nth(2)?.split_once(':')?;
+
+        let mount_dev = libc::makedev(major.parse().ok()?, minor.parse().ok()?);
+
+        match mount_dev == dev {
+            true => fs.split_whitespace().next().map(String::from),
+            false => None,
+        }
+    })
+}
@@ -1,76 +1,214 @@
+use std::io;
 use std::path::PathBuf;
 use std::sync::Arc;
 
 use thread_pool::ThreadPool;
 
-pub mod config;
-pub mod pattern;
-mod walk;
+use super::config::FinderConfig;
+use super::dupes::{DupeFinder, DupeGroup};
+use super::pattern::{FileType, Pattern};
+use super::usage::{DirUsage, DiskUsage};
+use super::walk::{self, DirJob, DirVisitor, FileInfo};
 
-use config::FinderConfig;
-use pattern::{FileType, Pattern};
+#[cfg(target_os = "linux")]
+use super::watch::Watcher;
 
 
-#[derive(Debug, Default)]
 pub struct Finder {
-    root: PathBuf,
-    config: FinderConfig,
-    pool: Option<ThreadPool<PathBuf>>,
+    pub(crate) root: PathBuf,
+    pub(crate) config: FinderConfig,
+    pub(crate) pool: Option<ThreadPool<FileInfo>>,
 }
 
 
 impl Finder {
-    pub fn from_new_root(&mut self, root: PathBuf) -> Self {
+    pub fn from_new_root(mut self, root: PathBuf) -> Self {
         self.root = root;
-        *self
+        self
     }
 
-    pub fn add_pattern(&mut self, pattern: Pattern) -> Self {
-        self.config.with_pattern(pattern);
-        *self
+    pub fn add_pattern(mut self, pattern: Pattern) -> Self {
+        self.config = self.config.with_pattern(pattern);
+        self
     }
 
-    pub fn add_patterns<L>(&mut self, patterns: L) -> Self
+    pub fn add_patterns<L>(mut self, patterns: L) -> Self
     where
         L: Into<Vec<Pattern>>
     {
-        self.config.with_patterns(patterns);
-        *self
+        self.config = self.config.with_patterns(patterns);
+        self
     }
 
-    pub fn clear_patterns(&mut self) -> Self {
-        self.config.clear_patterns();
-        *self
+    pub fn clear_patterns(mut self) -> Self {
+        self.config = self.config.clear_patterns();
+        self
     }
 
-    pub fn file_type(&mut self, file_type: FileType) -> Self {
-        self.config.file_type(file_type);
-        *self
+    pub fn file_type(mut self, file_type: FileType) -> Self {
+        self.config = self.config.file_type(file_type);
+        self
     }
 
-    pub fn file_types<L>(&mut self, file_types: L) -> Self
+    pub fn file_types<L>(mut self, file_types: L) -> Self
     where
         L: Into<Vec<FileType>>
     {
-        self.config.file_types(file_types);
-        *self
+        self.config = self.config.file_types(file_types);
+        self
     }
 
-    pub fn clear_file_types(&mut self) -> Self {
-        self.config.clear_file_types();
-        *self
+    pub fn clear_file_types(mut self) -> Self {
+        self.config = self.config.clear_file_types();
+        self
     }
 
+    /// Queues up the root directory, which in turn queues up every directory below it.
     pub fn start(&mut self) {
-        let pool = Arc::new(self.pool.expect("Thread pool not initialized"));
+        let pool = self.pool.as_ref().expect("Thread pool not initialized");
 
+        pool.spawn(walk::recurse_find(self.root_job(), Arc::new(self.config.clone())))
+            .expect("Could not submit the root directory");
     }
 
-    pub fn collect(&mut self) {
+    fn root_job(&self) -> DirJob {
+        DirJob::for_root(self.root.clone(), &self.config)
+    }
+
+    // For walks that run on a pool of their own. The pool the finder came with hasn't been
+    // used, so it's shut down rather than left hanging around.
+    fn release_pool(&mut self) {
+        if let Some(mut pool) = self.pool.take() {
+            pool.join();
+        }
+    }
+
+    // Starts a walk whose results go through a visitor
+    fn visit<V: DirVisitor>(&mut self, visitor: V) -> ThreadPool<V::Output> {
+        self.release_pool();
+
+        let pool = ThreadPool::create_pool(self.config.pool_size);
+
+        pool.spawn(walk::recurse_visit(self.root_job(), Arc::new(self.config.clone()), Arc::new(visitor)))
+            .expect("Could not submit the root directory");
+
+        pool
+    }
+
+    /// Starts the search, and returns an iterator over the results. Matches are yielded
+    /// as soon as a worker finds them, and the iterator ends once every directory under
+    /// the root has been searched. With a `limit` and no `order_by`, it ends (and stops
+    /// the search) after that many instead. With both, every match is yielded, since the
+    /// best ones can't be known until the end. Use `collect` for those.
+    pub fn iter(self) -> FinderIter {
+        let limit = match self.config.order_by {
+            Some(_) => None,
+            None => self.config.limit,
+        };
+
+        self.stream(limit)
+    }
+
+    fn stream(mut self, limit: Option<usize>) -> FinderIter {
+        self.start();
+
+        FinderIter {pool: self.pool.take(), remaining: limit}
+    }
+
+    /// Runs the search to completion and returns every result, sorted if the config
+    /// specified an `order_by`. With a `limit` and no `order_by`, the search stops as soon
+    /// as it has enough.
+    pub fn collect(self) -> Vec<FileInfo> {
+        let config = self.config.clone();
+
+        config.arrange(self.iter())
+    }
+
+    /// Runs the search to completion, and returns the groups of duplicate files among
+    /// the results. Any `limit` is ignored, since cutting the files short would miss copies.
+    pub fn find_dupes(self, dupes: &DupeFinder) -> Vec<DupeGroup> {
+        dupes.find(self.stream(None))
+    }
+
+    /// Totals up the size of every directory under the root while the search runs. Only
+    /// entries that match count towards the totals, and any `limit` is ignored.
+    ///
+    /// Each worker adds up the directory it just read, so the calling thread only gets one
+    /// partial total per directory, and just has to add those up the tree.
+    pub fn disk_usage(mut self, usage: &DiskUsage) -> Vec<DirUsage> {
+        let mut pool = self.visit(usage.counter());
 
+        let totals = usage.total(&self.root, std::iter::from_fn(|| pool.recv_result()));
+        pool.join();
+
+        totals
+    }
+
+    /// Turns the search into one that keeps running, and reports files as they start and
+    /// stop matching. Call `start` on the watcher for the initial matches. Any `limit` is
+    /// ignored.
+    #[cfg(target_os = "linux")]
+    pub fn watch(mut self) -> io::Result<Watcher> {
+        self.release_pool();
+
+        Watcher::new(self.root, self.config)
+    }
+}
+
+impl IntoIterator for Finder {
+    type Item = FileInfo;
+    type IntoIter = FinderIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
+}
 
 
+/// Streams results out of a running search. Results aren't held onto, so memory use stays
+/// flat no matter how many files match.
+pub struct FinderIter {
+    // Taken (and joined) once the search is finished.
+    pool: Option<ThreadPool<FileInfo>>,
+    // How many more results to yield, if there's a limit
+    remaining: Option<usize>,
+}
 
+impl Iterator for FinderIter {
+    type Item = FileInfo;
 
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == Some(0) {
+            // Got enough, so the rest of the tree can be skipped
+            if let Some(mut pool) = self.pool.take() {
+                pool.cancel();
+            }
+
+            return None;
+        }
+
+        let next = self.pool.as_ref()?.recv_result();
+
+        if let (Some(remaining), Some(_)) = (&mut self.remaining, &next) {
+            *remaining -= 1;
+        }
+
+        if next.is_none() {
+            if let Some(mut pool) = self.pool.take() {
+                pool.join();
+            }
+        }
+
+        next
+    }
+}
+
+impl Drop for FinderIter {
+    fn drop(&mut self) {
+        // Dropped before the search finished, so skip whatever is left instead of walking
+        // the rest of the tree for nothing.
+        if let Some(mut pool) = self.pool.take() {
+            pool.cancel();
+        }
+    }
 }
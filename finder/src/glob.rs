@@ -0,0 +1,238 @@
+use std::fmt;
+use std::path::Path;
+
+use regex::Regex;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GlobSyntax {
+    // Shell globs, with `{a,b}` alternatives
+    Shell,
+    // Ignore file rules, where braces are literal, and a pattern without a slash (other than
+    // a trailing one) can match at any depth
+    Gitignore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlobMode {
+    // Matched against just the file name, like `find -name`
+    Name,
+    // Matched against the whole path, like `find -path`
+    Path,
+}
+
+
+/// A shell style glob. `*` and `?` never match a '/', `**` matches across directories when
+/// it's a full path component, and `{a,b}` matches either alternative. `[...]` classes
+/// work the same as they do in ignore files.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    source: String,
+    regex: Regex,
+    mode: GlobMode,
+}
+
+impl Glob {
+    /// Picks the mode from the pattern. Anything with a '/' in it is matched against the
+    /// full path, and anything else against the file name.
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        match pattern.contains('/') {
+            true => Self::path(pattern),
+            false => Self::name(pattern),
+        }
+    }
+
+    pub fn name(pattern: &str) -> Result<Self, regex::Error> {
+        Self::with_mode(pattern, GlobMode::Name)
+    }
+
+    /// A glob for the full path. Absolute patterns have to match the whole path, where
+    /// relative ones only need to match its last few components, so `src/*.rs` finds
+    /// `/home/me/project/src/main.rs`.
+    pub fn path(pattern: &str) -> Result<Self, regex::Error> {
+        Self::with_mode(pattern, GlobMode::Path)
+    }
+
+    fn with_mode(pattern: &str, mode: GlobMode) -> Result<Self, regex::Error> {
+        // A relative path glob is the same as one starting with "**/"
+        let full_pattern = match mode {
+            GlobMode::Path if !pattern.starts_with('/') && !pattern.starts_with("**") => format!("**/{}", pattern),
+            _ => pattern.to_string(),
+        };
+
+        let regex = Regex::new(&glob_to_regex(&full_pattern, GlobSyntax::Shell))?;
+
+        Ok(Self {source: pattern.to_string(), regex, mode})
+    }
+
+    pub fn mode(&self) -> GlobMode {
+        self.mode
+    }
+
+    pub fn is_match(&self, path: &Path) -> bool {
+        let target = match self.mode {
+            GlobMode::Name => path.file_name().and_then(|name| name.to_str()),
+            GlobMode::Path => path.to_str(),
+        };
+
+        target.map(|target| self.regex.is_match(target)).unwrap_or(false)
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+
+/// Translates a glob into a regex that has to match the whole string. `*` and `?` never match
+/// a '/', while `**` matches across directories when it's a full path component. Ignore file
+/// rules are matched against the path from the file's own directory, and anchored to it if
+/// they have a slash in them.
+pub(crate) fn glob_to_regex(pattern: &str, syntax: GlobSyntax) -> String {
+    let mut regex_str = String::from("^");
+
+    let pattern = match syntax {
+        GlobSyntax::Gitignore if pattern.contains('/') => pattern.trim_start_matches('/'),
+        GlobSyntax::Gitignore => {
+            regex_str.push_str("(?:.*/)?");
+            pattern
+        },
+        GlobSyntax::Shell => pattern,
+    };
+
+    let chars: Vec<char> = pattern.chars().collect();
+
+    // How many `{` we're inside of, so we know whether a ',' is an alternative or a literal
+    let mut brace_depth = 0;
+
+    let mut idx = 0;
+    while idx < chars.len() {
+        match chars[idx] {
+            '*' if chars.get(idx + 1) == Some(&'*') => {
+                let at_start = idx == 0 || chars[idx - 1] == '/';
+                let next = chars.get(idx + 2);
+
+                match (at_start, next) {
+                    // "**/" - zero or more directories
+                    (true, Some('/')) => {
+                        regex_str.push_str("(?:.*/)?");
+                        idx += 3;
+                    },
+                    // A trailing (or lone) "**" - everything below
+                    (true, None) => {
+                        regex_str.push_str(".*");
+                        idx += 2;
+                    },
+                    // Anywhere else it's just a regular '*'
+                    _ => {
+                        regex_str.push_str("[^/]*");
+                        idx += 2;
+                    },
+                }
+                continue;
+            },
+            '*' => regex_str.push_str("[^/]*"),
+            '?' => regex_str.push_str("[^/]"),
+            '[' => {
+                match parse_class(&chars[idx..]) {
+                    Some((class, len)) => {
+                        regex_str.push_str(&class);
+                        idx += len;
+                        continue;
+                    },
+                    // No closing bracket, so treat it as a literal
+                    None => regex_str.push_str(r"\["),
+                }
+            },
+            // Like the shell, a '{' without a matching '}' is just a '{'
+            '{' if syntax == GlobSyntax::Shell && has_closing_brace(&chars[idx..]) => {
+                regex_str.push_str("(?:");
+                brace_depth += 1;
+            },
+            ',' if brace_depth > 0 => regex_str.push('|'),
+            '}' if brace_depth > 0 => {
+                regex_str.push(')');
+                brace_depth -= 1;
+            },
+            '\\' if idx + 1 < chars.len() => {
+                idx += 1;
+                regex_str.push_str(&regex::escape(&chars[idx].to_string()));
+            },
+            other => regex_str.push_str(&regex::escape(&other.to_string())),
+        }
+
+        idx += 1;
+    }
+
+    regex_str.push('$');
+    regex_str
+}
+
+/// Parses a `[...]` class starting at `chars[0]`, returning the regex version and the number
+/// of chars used up, or None if the class is never closed.
+fn parse_class(chars: &[char]) -> Option<(String, usize)> {
+    let mut class = String::from("[");
+    let mut idx = 1;
+
+    if let Some('!') | Some('^') = chars.get(idx) {
+        class.push('^');
+        idx += 1;
+    }
+
+    // A ']' right after the opening bracket is a literal
+    if chars.get(idx) == Some(&']') {
+        class.push_str(r"\]");
+        idx += 1;
+    }
+
+    while idx < chars.len() {
+        match chars[idx] {
+            ']' => {
+                class.push(']');
+                return Some((class, idx + 1));
+            },
+            '\\' if idx + 1 < chars.len() => {
+                idx += 1;
+                class.push('\\');
+                class.push(chars[idx]);
+            },
+            '-' => class.push('-'),
+            // Escape anything else the regex crate might treat specially inside a class
+            other if "[]^&~".contains(other) => {
+                class.push('\\');
+                class.push(other);
+            },
+            other => class.push(other),
+        }
+
+        idx += 1;
+    }
+
+    None
+}
+
+// Whether the '{' at `chars[0]` is closed, counting any nested braces
+fn has_closing_brace(chars: &[char]) -> bool {
+    let mut depth = 0;
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        match chars[idx] {
+            '\\' => idx += 1,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return true;
+                }
+            },
+            _ => (),
+        }
+
+        idx += 1;
+    }
+
+    false
+}
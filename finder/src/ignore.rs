@@ -0,0 +1,204 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use regex::Regex;
+
+use super::glob::{glob_to_regex, GlobSyntax};
+
+
+/// Ignore files read in each directory, lowest precedence first. Rules in a later file win
+/// over rules in an earlier one, so a `.findignore` can un-ignore something from `.gitignore`.
+pub static IGNORE_FILE_NAMES: &[&str] = &[".gitignore", ".ignore", ".findignore"];
+
+// Relative to a directory containing a `.git` dir. Lower precedence than any ignore file.
+static GIT_EXCLUDE_PATH: &str = ".git/info/exclude";
+
+
+#[derive(Debug)]
+struct IgnoreRule {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl IgnoreRule {
+    /// Parses a single line from an ignore file, following the gitignore format.
+    fn parse(line: &str) -> Option<Self> {
+        let mut line = line.trim_end_matches(&['\n', '\r'][..]);
+
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        // Trailing spaces are dropped, unless they're escaped with a backslash
+        while line.ends_with(' ') && !line.ends_with("\\ ") {
+            line = &line[..line.len() - 1];
+        }
+
+        let negated = line.starts_with('!');
+        if negated {
+            line = &line[1..];
+        }
+
+        let dir_only = line.ends_with('/');
+        if dir_only {
+            line = line.trim_end_matches('/');
+        }
+
+        if line.is_empty() {
+            return None;
+        }
+
+        Regex::new(&glob_to_regex(line, GlobSyntax::Gitignore)).ok().map(|regex| IgnoreRule {regex, negated, dir_only})
+    }
+
+    fn matches(&self, rel_path: &str, is_dir: bool) -> bool {
+        (is_dir || !self.dir_only) && self.regex.is_match(rel_path)
+    }
+}
+
+
+/// The rules from a single ignore file, along with where they apply from.
+#[derive(Debug)]
+struct IgnoreFile {
+    // The directory the file was found in, as the walker sees it.
+    base: PathBuf,
+    // For files above the search root, the path from their directory down to the root.
+    prefix: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreFile {
+    fn read(path: &Path, base: PathBuf, prefix: PathBuf) -> Option<Self> {
+        let contents = fs::read_to_string(path).ok()?;
+
+        let rules: Vec<IgnoreRule> = contents.lines()
+            .filter_map(IgnoreRule::parse)
+            .collect();
+
+        match rules.is_empty() {
+            true => None,
+            false => Some(IgnoreFile {base, prefix, rules}),
+        }
+    }
+
+    /// Some(true) if the path is ignored, Some(false) if it's explicitly un-ignored by a
+    /// negated rule, or None if no rule in this file matched.
+    fn check(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let rel_path = self.prefix.join(path.strip_prefix(&self.base).ok()?);
+        let rel_str = path_to_slashes(&rel_path);
+
+        // The last matching rule wins
+        self.rules.iter()
+            .rev()
+            .find(|rule| rule.matches(&rel_str, is_dir))
+            .map(|rule| !rule.negated)
+    }
+}
+
+fn path_to_slashes(path: &Path) -> String {
+    let parts: Vec<String> = path.components()
+        .map(|part| part.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    parts.join("/")
+}
+
+
+#[derive(Debug)]
+struct IgnoreNode {
+    // Highest precedence last
+    files: Vec<IgnoreFile>,
+    parent: Option<Arc<IgnoreNode>>,
+}
+
+
+/// The ignore files that apply to a directory, including every one found in its parents.
+/// Cloning is cheap, so each directory job can carry its own copy.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreStack {
+    head: Option<Arc<IgnoreNode>>,
+}
+
+impl IgnoreStack {
+    /// Builds the stack for the root of a search from the ignore files in the directories
+    /// above it, up to the top of the git repository it's in. Outside of a repository,
+    /// nothing above the root applies. The root's own files get added when it's searched,
+    /// like any other directory.
+    pub fn for_root(root: &Path) -> Self {
+        let abs_root = match fs::canonicalize(root) {
+            Ok(abs_root) => abs_root,
+            Err(_) => return Self::default(),
+        };
+
+        let repo_root = match abs_root.ancestors().find(|dir| dir.join(".git").exists()) {
+            Some(repo_root) => repo_root,
+            None => return Self::default(),
+        };
+
+        let mut stack = Self::default();
+
+        // Walk down from the top, so the closest directories end up with the highest precedence
+        let ancestors: Vec<&Path> = abs_root.ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(repo_root))
+            .collect();
+        for ancestor in ancestors.into_iter().rev() {
+            let prefix = abs_root.strip_prefix(ancestor).unwrap_or(&abs_root).to_path_buf();
+            stack = stack.push_dir(ancestor, root.to_path_buf(), prefix, |name| ancestor.join(name).exists());
+        }
+
+        stack
+    }
+
+    /// Returns the stack for a directory being searched, adding any ignore files it
+    /// contains. `has_entry` is asked whether a given name exists in the directory, so we
+    /// don't have to try and open files that aren't there.
+    pub fn enter_dir<F>(&self, dir: &Path, has_entry: F) -> Self
+    where
+        F: Fn(&str) -> bool
+    {
+        self.push_dir(dir, dir.to_path_buf(), PathBuf::new(), has_entry)
+    }
+
+    fn push_dir<F>(&self, dir: &Path, base: PathBuf, prefix: PathBuf, has_entry: F) -> Self
+    where
+        F: Fn(&str) -> bool
+    {
+        let mut files = vec![];
+
+        if has_entry(".git") {
+            files.extend(IgnoreFile::read(&dir.join(GIT_EXCLUDE_PATH), base.clone(), prefix.clone()));
+        }
+
+        for name in IGNORE_FILE_NAMES {
+            if has_entry(name) {
+                files.extend(IgnoreFile::read(&dir.join(name), base.clone(), prefix.clone()));
+            }
+        }
+
+        if files.is_empty() {
+            return self.clone();
+        }
+
+        Self {head: Some(Arc::new(IgnoreNode {files, parent: self.head.clone()}))}
+    }
+
+    /// Checks the closest ignore files first, and stops at the first one with an opinion.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut node = self.head.as_ref();
+
+        while let Some(current) = node {
+            for file in current.files.iter().rev() {
+                if let Some(ignored) = file.check(path, is_dir) {
+                    return ignored;
+                }
+            }
+
+            node = current.parent.as_ref();
+        }
+
+        false
+    }
+}
@@ -0,0 +1,695 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use thread_pool::{Task, ThreadPool, TaskResult};
+
+use super::config::{FinderConfig, FindOnly, OrderBy};
+use super::pattern::{self, Pattern, PatternCost, TimeField};
+use super::sniff::TypeDetection;
+use super::walk;
+
+
+// Bumped whenever the layout changes, so old indexes get rebuilt instead of misread
+static INDEX_MAGIC: &[u8] = b"FINDRS-INDEX\x02";
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    // Sockets, fifos, devices
+    Other,
+}
+
+impl EntryKind {
+    fn of(meta: &Metadata) -> Self {
+        let file_type = meta.file_type();
+
+        if file_type.is_dir() {EntryKind::Dir}
+        else if file_type.is_file() {EntryKind::File}
+        else if file_type.is_symlink() {EntryKind::Symlink}
+        else {EntryKind::Other}
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            EntryKind::File => 0,
+            EntryKind::Dir => 1,
+            EntryKind::Symlink => 2,
+            EntryKind::Other => 3,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(EntryKind::File),
+            1 => Some(EntryKind::Dir),
+            2 => Some(EntryKind::Symlink),
+            3 => Some(EntryKind::Other),
+            _ => None,
+        }
+    }
+}
+
+
+/// Everything the index keeps about a file besides its name, as it was when the index was
+/// built. Symlinks aren't followed.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryStat {
+    pub kind: EntryKind,
+    pub size: u64,
+    // Seconds and nanoseconds since the unix epoch
+    pub mtime: (i64, i64),
+    pub inode: u64,
+}
+
+impl EntryStat {
+    fn of(meta: &Metadata) -> Self {
+        Self {kind: EntryKind::of(meta), size: meta.len(), mtime: (meta.mtime(), meta.mtime_nsec()), inode: meta.ino()}
+    }
+
+    pub fn modified(&self) -> Option<SystemTime> {
+        let secs = u64::try_from(self.mtime.0).ok()?;
+        let nanos = u64::try_from(self.mtime.1).ok()?;
+
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs) + Duration::from_nanos(nanos))
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct IndexEntry {
+    pub name: OsString,
+    pub stat: EntryStat,
+}
+
+
+/// A match found in the index. It's built from the index alone, so it describes the file as
+/// it was at the last update.
+#[derive(Debug, Clone)]
+pub struct IndexMatch {
+    pub path: PathBuf,
+    // How many directories below the search root it is. Entries in the root are at 1.
+    pub depth: usize,
+    pub stat: EntryStat,
+    // How well a fuzzy pattern matched, if one did
+    pub score: Option<isize>,
+}
+
+
+#[derive(Debug, Clone)]
+struct DirRecord {
+    path: PathBuf,
+    // Seconds and nanoseconds. Anything added, removed or renamed in the directory changes it,
+    // so a directory with the same mtime as last time doesn't need to be read again.
+    mtime: (i64, i64),
+    entries: Vec<IndexEntry>,
+}
+
+
+/// A snapshot of every file under a root, saved to disk so searches can skip walking the
+/// tree, like `locate`. Entries are grouped by directory, with each directory's path only
+/// storing what's different from the one before it, which keeps the file small.
+#[derive(Debug, Clone)]
+pub struct Index {
+    root: PathBuf,
+    // Sorted by path, so everything under a directory comes right after it
+    dirs: Vec<DirRecord>,
+}
+
+impl Index {
+    /// Walks everything under the root, across the thread pool. Only the parts of the config
+    /// that decide where a walk goes are used: excluded files are left out without being
+    /// read, along with any filesystems it wouldn't go into. Everything else is left for the
+    /// searches.
+    pub fn build(root: &Path, config: &FinderConfig) -> io::Result<Self> {
+        let root = fs::canonicalize(root)?;
+        let dirs = scan(&root, HashMap::new(), config);
+
+        Ok(Self {root, dirs})
+    }
+
+    /// Brings the index up to date. Only directories whose mtime changed get read again,
+    /// everything else is carried over. Note that a file changing in place doesn't touch
+    /// its directory, so sizes and mtimes can lag behind until the directory changes. The
+    /// config is used the same way as in `build`, and isn't saved, so it's up to the caller
+    /// to pass the same one.
+    pub fn update(self, config: &FinderConfig) -> Self {
+        let previous: HashMap<PathBuf, DirRecord> = self.dirs.into_iter()
+            .map(|dir| (dir.path.clone(), dir))
+            .collect();
+
+        let dirs = scan(&self.root, previous, config);
+
+        Self {root: self.root, dirs}
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn n_dirs(&self) -> usize {
+        self.dirs.len()
+    }
+
+    pub fn n_entries(&self) -> usize {
+        self.dirs.iter().map(|dir| dir.entries.len()).sum()
+    }
+
+    /// Something in the config that can't be answered from the index alone, since it needs
+    /// the files themselves. Searches are only meant to be run with configs that have none.
+    pub fn unsupported(config: &FinderConfig) -> Option<&'static str> {
+        if config.follow_symlinks {
+            return Some("where symlinks point");
+        }
+
+        if config.use_ignore_files {
+            return Some("ignore files");
+        }
+
+        if !config.file_types.is_empty() && config.type_detection != TypeDetection::Extension {
+            return Some("file contents");
+        }
+
+        config.patterns.iter().find_map(unsupported_pattern)
+    }
+
+    /// Searches the index at `path` for matches under `root`. The index is read a directory
+    /// at a time rather than loaded, and matches come from what it says alone, so nothing
+    /// under the root is touched. The config shouldn't use anything that's `unsupported`.
+    pub fn search<'a>(path: &Path, config: &'a FinderConfig, root: &Path) -> io::Result<IndexSearch<'a>> {
+        let file = IndexFile::open(path)?;
+        let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+
+        Ok(IndexSearch {file, config, root, entry_depth: None, name: Vec::new(), seen_root: false})
+    }
+
+    /// Sorts the matches by `order_by` and cuts them down to the `limit`, the same as
+    /// `FinderConfig::arrange` does for a search.
+    pub fn arrange<I>(config: &FinderConfig, matches: I) -> Vec<IndexMatch>
+    where
+        I: IntoIterator<Item = IndexMatch>
+    {
+        let order_by = match (config.order_by, config.limit) {
+            (Some(order_by), _) => order_by,
+            (None, Some(limit)) => return matches.into_iter().take(limit).collect(),
+            (None, None) => return matches.into_iter().collect(),
+        };
+
+        let compare = |a: &IndexMatch, b: &IndexMatch| -> Ordering {
+            match order_by {
+                OrderBy::Size => a.stat.size.cmp(&b.stat.size),
+                OrderBy::Filename => a.path.file_name().cmp(&b.path.file_name()),
+                OrderBy::Score => a.score.cmp(&b.score),
+            }
+        };
+
+        let mut matches: Vec<IndexMatch> = matches.into_iter().collect();
+
+        match config.desc_order {
+            true => matches.sort_by(|a, b| compare(b, a)),
+            false => matches.sort_by(compare),
+        }
+
+        if let Some(limit) = config.limit {
+            matches.truncate(limit);
+        }
+
+        matches
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut file = IndexFile::open(path)?;
+        let mut dirs: Vec<DirRecord> = Vec::new();
+        let mut name: Vec<u8> = Vec::new();
+
+        while file.next_dir()? {
+            let mut entries = Vec::new();
+
+            while let Some(stat) = file.next_entry(&mut name)? {
+                entries.push(IndexEntry {name: OsStr::from_bytes(&name).to_os_string(), stat});
+            }
+
+            dirs.push(DirRecord {path: file.dir_path().to_path_buf(), mtime: file.dir_mtime, entries});
+        }
+
+        Ok(Self {root: file.root, dirs})
+    }
+
+    /// Writes the index out. It goes to a temporary file first, so a search never sees
+    /// half of one.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let tmp_path = path.with_extension("tmp");
+        let mut out = BufWriter::new(File::create(&tmp_path)?);
+
+        out.write_all(INDEX_MAGIC)?;
+
+        let root_bytes = self.root.as_os_str().as_bytes();
+        write_varint(&mut out, root_bytes.len() as u64)?;
+        out.write_all(root_bytes)?;
+
+        write_varint(&mut out, self.dirs.len() as u64)?;
+
+        let mut prev_path: &[u8] = &[];
+
+        for dir in &self.dirs {
+            let path_bytes = dir.path.as_os_str().as_bytes();
+            let shared = path_bytes.iter().zip(prev_path).take_while(|(a, b)| a == b).count();
+
+            write_varint(&mut out, shared as u64)?;
+            write_varint(&mut out, (path_bytes.len() - shared) as u64)?;
+            out.write_all(&path_bytes[shared..])?;
+
+            write_signed_varint(&mut out, dir.mtime.0)?;
+            write_signed_varint(&mut out, dir.mtime.1)?;
+
+            write_varint(&mut out, dir.entries.len() as u64)?;
+
+            for entry in &dir.entries {
+                let name = entry.name.as_bytes();
+                write_varint(&mut out, name.len() as u64)?;
+                out.write_all(name)?;
+                out.write_all(&[entry.stat.kind.to_byte()])?;
+                write_varint(&mut out, entry.stat.size)?;
+                write_signed_varint(&mut out, entry.stat.mtime.0)?;
+                write_signed_varint(&mut out, entry.stat.mtime.1)?;
+                write_varint(&mut out, entry.stat.inode)?;
+            }
+
+            prev_path = path_bytes;
+        }
+
+        out.into_inner().map_err(|err| err.into_error())?.sync_all()?;
+        fs::rename(&tmp_path, path)
+    }
+}
+
+
+// Reads every directory under the root, reusing the previous record for any that haven't changed
+fn scan(root: &Path, previous: HashMap<PathBuf, DirRecord>, config: &FinderConfig) -> Vec<DirRecord> {
+    let mut pool: ThreadPool<DirRecord> = ThreadPool::create_pool(config.pool_size);
+    let previous = Arc::new(previous);
+    let config = Arc::new(config.clone());
+
+    pool.spawn(index_dir(root.to_path_buf(), None, config, previous))
+        .expect("Could not submit the root directory");
+
+    let mut dirs = vec![];
+    while let Some(dir) = pool.recv_result() {
+        dirs.push(dir);
+    }
+
+    pool.join();
+
+    dirs.sort_by(|a, b| a.path.cmp(&b.path));
+    dirs
+}
+
+fn index_dir(path: PathBuf, parent_dev: Option<u64>, config: Arc<FinderConfig>, previous: Arc<HashMap<PathBuf, DirRecord>>)
+    -> impl FnOnce() -> Option<TaskResult<DirRecord>> + Send + 'static
+{
+    move || {
+        let meta = fs::symlink_metadata(&path).ok()?;
+
+        // Left out entirely, though it's still listed in its parent
+        if parent_dev.map(|parent_dev| !config.can_enter_dir(&path, &meta, parent_dev)).unwrap_or(false) {
+            return None;
+        }
+        let mtime = (meta.mtime(), meta.mtime_nsec());
+
+        let entries = match previous.get(&path) {
+            Some(record) if record.mtime == mtime => record.entries.clone(),
+            _ => {
+                let mut entries: Vec<IndexEntry> = walk::get_dir_entries(path.clone())?
+                    .filter(|entry| !config.is_excluded(&entry.path()))
+                    .filter_map(|entry| entry.metadata().ok().map(|meta| IndexEntry {name: entry.file_name(), stat: EntryStat::of(&meta)}))
+                    .collect();
+
+                entries.sort_by(|a, b| a.name.cmp(&b.name));
+                entries
+            },
+        };
+
+        let sub_dirs: Vec<Task<DirRecord>> = entries.iter()
+            .filter(|entry| entry.stat.kind == EntryKind::Dir)
+            .map(|entry| Box::new(index_dir(path.join(&entry.name), Some(meta.dev()), config.clone(), previous.clone())) as Task<DirRecord>)
+            .collect();
+
+        TaskResult::from_result(DirRecord {path, mtime, entries})
+            .add_jobs(sub_dirs)
+            .to_opt()
+    }
+}
+
+
+/// Reads through an index file as it's searched. Yields an error (and then stops) if the
+/// file turns out to be corrupt.
+pub struct IndexSearch<'a> {
+    file: IndexFile<BufReader<File>>,
+    config: &'a FinderConfig,
+    root: PathBuf,
+    // Set while going through the entries of a directory under the root
+    entry_depth: Option<usize>,
+    // The current entry's name, kept around so it doesn't need allocating every time
+    name: Vec<u8>,
+    seen_root: bool,
+}
+
+impl IndexSearch<'_> {
+    fn find_next(&mut self) -> io::Result<Option<IndexMatch>> {
+        loop {
+            if let Some(depth) = self.entry_depth {
+                match self.file.next_entry(&mut self.name)? {
+                    Some(stat) => {
+                        let path = self.file.dir_path().join(OsStr::from_bytes(&self.name));
+
+                        if let Some(found) = match_entry(self.config, path, stat, depth) {
+                            return Ok(Some(found));
+                        }
+                    },
+                    None => self.entry_depth = None,
+                }
+
+                continue;
+            }
+
+            if !self.file.next_dir()? {
+                return Ok(None);
+            }
+
+            let dir = self.file.dir_path();
+
+            // Directories are sorted, so everything under the root comes in one run
+            if !dir.starts_with(&self.root) {
+                match self.seen_root {
+                    true => return Ok(None),
+                    false => continue,
+                }
+            }
+
+            self.seen_root = true;
+
+            if !is_pruned(self.config, &self.root, dir) {
+                // Entries are one deeper than the directory they're in
+                let dir_depth = dir.strip_prefix(&self.root).map(|rel| rel.components().count()).unwrap_or(0);
+                self.entry_depth = Some(dir_depth + 1);
+            }
+        }
+    }
+}
+
+impl Iterator for IndexSearch<'_> {
+    type Item = io::Result<IndexMatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.find_next() {
+            Ok(found) => found.map(Ok),
+            Err(err) => {
+                // Nothing after a corrupt spot can be trusted
+                self.entry_depth = None;
+                self.seen_root = true;
+                self.file.dirs_left = 0;
+                Some(Err(err))
+            },
+        }
+    }
+}
+
+
+// Whether the directory, or anything between it and the search root, was excluded. A walk
+// would never have gone into it.
+fn is_pruned(config: &FinderConfig, root: &Path, dir: &Path) -> bool {
+    !config.excludes.is_empty() && dir.ancestors()
+        .take_while(|ancestor| *ancestor != root)
+        .any(|ancestor| config.is_excluded(ancestor))
+}
+
+fn unsupported_pattern(pattern: &Pattern) -> Option<&'static str> {
+    match pattern {
+        Pattern::Ext(_) | Pattern::Regex(_) | Pattern::Glob(_) | Pattern::Fuzzy(..) | Pattern::FileType(_) => None,
+        Pattern::Newer(TimeField::Modified, _) | Pattern::Older(TimeField::Modified, _) => None,
+        Pattern::Newer(..) | Pattern::Older(..) => Some("access, change or birth times"),
+        Pattern::Uid(_) | Pattern::Gid(_) | Pattern::NoUser(_) | Pattern::NoGroup(_) => Some("owners"),
+        Pattern::Perm(_) => Some("permissions"),
+        Pattern::Contains(_) => Some("file contents"),
+        Pattern::And(patterns) | Pattern::Or(patterns) => patterns.iter().find_map(unsupported_pattern),
+        Pattern::Not(pattern) => unsupported_pattern(pattern),
+    }
+}
+
+// Checks an entry the same way `FinderConfig::is_match` checks a file, going only by what's
+// in the index
+fn match_entry(config: &FinderConfig, path: PathBuf, stat: EntryStat, depth: usize) -> Option<IndexMatch> {
+    if config.is_excluded(&path) {
+        return None;
+    }
+
+    if config.min_depth.map(|min_depth| depth < min_depth).unwrap_or(false)
+        || config.max_depth.map(|max_depth| depth > max_depth).unwrap_or(false) {
+        return None;
+    }
+
+    match config.find_only {
+        Some(FindOnly::Files) if stat.kind != EntryKind::File => return None,
+        Some(FindOnly::Directories) if stat.kind != EntryKind::Dir => return None,
+        _ => (),
+    }
+
+    // Same as a normal search, directories never pass a size limit
+    let has_size_limit = config.min_size.is_some() || config.max_size.is_some();
+
+    if has_size_limit && (stat.kind == EntryKind::Dir
+        || config.min_size.map(|min_size| stat.size < min_size).unwrap_or(false)
+        || config.max_size.map(|max_size| stat.size > max_size).unwrap_or(false)) {
+        return None;
+    }
+
+    let is_filter = |pattern: &Pattern| pattern.is_metadata_filter() || pattern.is_combinator();
+
+    let mut names = config.patterns.iter()
+        .filter(|pattern| !is_filter(pattern) && pattern.cost() == PatternCost::Name)
+        .peekable();
+
+    if names.peek().is_some() && !names.any(|pattern| entry_matches(pattern, &path, &stat)) {
+        return None;
+    }
+
+    let passed_filters = config.patterns.iter()
+        .filter(|pattern| is_filter(pattern))
+        .all(|pattern| entry_matches(pattern, &path, &stat));
+
+    if !passed_filters {
+        return None;
+    }
+
+    if !config.file_types.is_empty() && !config.file_types.iter().any(|f_type| f_type.matches_file(&path)) {
+        return None;
+    }
+
+    let score = config.patterns.iter()
+        .find_map(|pattern| match pattern {
+            Pattern::Fuzzy(fuzzy, thresh) => Some(pattern::fuzzy_score_path(&path, fuzzy)).filter(|score| score > thresh),
+            _ => None,
+        });
+
+    Some(IndexMatch {path, depth, stat, score})
+}
+
+fn entry_matches(pattern: &Pattern, path: &Path, stat: &EntryStat) -> bool {
+    match pattern {
+        Pattern::Newer(TimeField::Modified, time) => stat.modified().map(|found| found > *time).unwrap_or(false),
+        Pattern::Older(TimeField::Modified, time) => stat.modified().map(|found| found < *time).unwrap_or(false),
+        Pattern::And(patterns) => patterns.iter().all(|pattern| entry_matches(pattern, path, stat)),
+        Pattern::Or(patterns) => patterns.iter().any(|pattern| entry_matches(pattern, path, stat)),
+        Pattern::Not(pattern) => !entry_matches(pattern, path, stat),
+        // Anything else left is matched on the path
+        _ => pattern.matches(path),
+    }
+}
+
+
+// Steps through an index file a directory at a time, so it never has to be held in memory
+// all at once
+struct IndexFile<R> {
+    reader: Reader<R>,
+    root: PathBuf,
+    dirs_left: u64,
+    entries_left: u64,
+    // Each directory's path is stored as what's different from the one before it
+    dir_path: Vec<u8>,
+    dir_mtime: (i64, i64),
+}
+
+impl IndexFile<BufReader<File>> {
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut reader = Reader {input: BufReader::new(File::open(path)?)};
+        let mut buf = Vec::new();
+
+        reader.bytes_into(INDEX_MAGIC.len(), &mut buf)?;
+        if buf != INDEX_MAGIC {
+            return Err(corrupt());
+        }
+
+        let root_len = reader.varint()? as usize;
+        reader.bytes_into(root_len, &mut buf)?;
+        let root = PathBuf::from(OsStr::from_bytes(&buf));
+
+        let dirs_left = reader.varint()?;
+
+        Ok(Self {reader, root, dirs_left, entries_left: 0, dir_path: Vec::new(), dir_mtime: (0, 0)})
+    }
+}
+
+impl<R: Read> IndexFile<R> {
+    fn dir_path(&self) -> &Path {
+        Path::new(OsStr::from_bytes(&self.dir_path))
+    }
+
+    // Moves on to the next directory, skipping whatever's left of the current one. False
+    // once there aren't any more.
+    fn next_dir(&mut self) -> io::Result<bool> {
+        let mut name = Vec::new();
+        while self.next_entry(&mut name)?.is_some() {}
+
+        if self.dirs_left == 0 {
+            return Ok(false);
+        }
+
+        let shared = self.reader.varint()? as usize;
+        let suffix_len = self.reader.varint()? as usize;
+
+        if shared > self.dir_path.len() {
+            return Err(corrupt());
+        }
+
+        let mut suffix = Vec::new();
+        self.reader.bytes_into(suffix_len, &mut suffix)?;
+
+        self.dir_path.truncate(shared);
+        self.dir_path.extend_from_slice(&suffix);
+
+        self.dir_mtime = (self.reader.signed_varint()?, self.reader.signed_varint()?);
+        self.entries_left = self.reader.varint()?;
+        self.dirs_left -= 1;
+
+        Ok(true)
+    }
+
+    // The next entry in the current directory, with its name put in `name`. None once the
+    // directory is done.
+    fn next_entry(&mut self, name: &mut Vec<u8>) -> io::Result<Option<EntryStat>> {
+        if self.entries_left == 0 {
+            return Ok(None);
+        }
+
+        let name_len = self.reader.varint()? as usize;
+        self.reader.bytes_into(name_len, name)?;
+
+        let kind = EntryKind::from_byte(self.reader.byte()?).ok_or_else(corrupt)?;
+
+        let stat = EntryStat {
+            kind,
+            size: self.reader.varint()?,
+            mtime: (self.reader.signed_varint()?, self.reader.signed_varint()?),
+            inode: self.reader.varint()?,
+        };
+
+        self.entries_left -= 1;
+        Ok(Some(stat))
+    }
+}
+
+
+struct Reader<R> {
+    input: R,
+}
+
+impl<R: Read> Reader<R> {
+    // Reads exactly `len` bytes into `buf`, replacing what was there. A length past the end
+    // of the file only reads as much as there is, so a corrupt one can't ask for a huge
+    // allocation.
+    fn bytes_into(&mut self, len: usize, buf: &mut Vec<u8>) -> io::Result<()> {
+        buf.clear();
+        (&mut self.input).take(len as u64).read_to_end(buf)?;
+
+        match buf.len() == len {
+            true => Ok(()),
+            false => Err(corrupt()),
+        }
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        let mut byte = [0u8; 1];
+
+        match self.input.read_exact(&mut byte) {
+            Ok(()) => Ok(byte[0]),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Err(corrupt()),
+            Err(err) => Err(err),
+        }
+    }
+
+    // LEB128, 7 bits at a time with the high bit set on every byte but the last
+    fn varint(&mut self) -> io::Result<u64> {
+        let mut val: u64 = 0;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            val |= ((byte & 0x7F) as u64) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(val);
+            }
+        }
+
+        Err(corrupt())
+    }
+
+    fn signed_varint(&mut self) -> io::Result<i64> {
+        let val = self.varint()?;
+        Ok(((val >> 1) as i64) ^ -((val & 1) as i64))
+    }
+}
+
+fn write_varint<W: Write>(out: &mut W, mut val: u64) -> io::Result<()> {
+    let mut buf = [0u8; 10];
+    let mut len = 0;
+
+    loop {
+        let byte = (val & 0x7F) as u8;
+        val >>= 7;
+
+        if val == 0 {
+            buf[len] = byte;
+            len += 1;
+            break;
+        }
+
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+
+    out.write_all(&buf[..len])
+}
+
+// Zigzag encoded, so small negative numbers stay small
+fn write_signed_varint<W: Write>(out: &mut W, val: i64) -> io::Result<()> {
+    write_varint(out, ((val << 1) ^ (val >> 63)) as u64)
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "The index is corrupt, or from a different version of find-rs")
+}
@@ -1,10 +1,955 @@
 pub mod finder;
 pub mod config;
+pub mod content;
+pub mod dupes;
+pub mod exclude;
+pub mod filesystem;
+pub mod glob;
+pub mod ignore;
+#[cfg(unix)]
+pub mod index;
 pub mod pattern;
+pub mod sniff;
+pub mod usage;
+pub mod users;
 pub mod walk;
+#[cfg(target_os = "linux")]
+pub mod watch;
 
 
-pub use finder::Finder;
-pub use config::FinderConfig;
-pub use pattern::{FileType, Pattern};
+pub use finder::{Finder, FinderIter};
+pub use config::{FinderConfig, FindOnly, OrderBy};
+pub use content::{ContentSearch, MatchedLine};
+pub use dupes::{DupeFinder, DupeGroup};
+pub use exclude::Exclude;
+pub use filesystem::FsKind;
+pub use glob::{Glob, GlobMode};
+#[cfg(unix)]
+pub use index::{EntryKind, EntryStat, Index, IndexEntry, IndexMatch, IndexSearch};
+pub use pattern::{fuzzy_match, fuzzy_match_path, CustomType, FileType, Pattern, PatternCost, PermMask, TimeField};
+pub use sniff::TypeDetection;
+pub use usage::{DirUsage, DiskUsage, SizeMode, UsageTotals};
 pub use walk::FileInfo;
+#[cfg(target_os = "linux")]
+pub use watch::{WatchEvent, Watcher};
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    use regex::Regex;
+
+    // A directory under the temp dir, unique to this test process + name. It's removed when
+    // dropped, so a failing test doesn't leave it behind.
+    struct TestDir {
+        path: PathBuf,
+    }
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("finder-test-{}-{}", process::id(), name));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).expect("Could not create test dir");
+
+            Self {path}
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+
+    // Builds a small tree in a new test dir.
+    fn make_tree(name: &str, n_dirs: usize, files_per_dir: usize) -> TestDir {
+        let test_dir = TestDir::new(name);
+
+        for dir_idx in 0..n_dirs {
+            let dir = test_dir.path.join(format!("dir{}", dir_idx)).join("nested");
+            fs::create_dir_all(&dir).expect("Could not create test dir");
+
+            for file_idx in 0..files_per_dir {
+                fs::write(dir.join(format!("file{}.txt", file_idx)), b"data").expect("Could not create test file");
+            }
+        }
+
+        test_dir
+    }
+
+    #[test]
+    fn iter_yields_every_match() {
+        let test_dir = make_tree("iter", 8, 5);
+        let root = test_dir.path.clone();
+
+        let finder = FinderConfig::from(&root)
+            .with_pool_size(4)
+            .with_find_only(FindOnly::Files)
+            .build();
+
+        let found: Vec<FileInfo> = finder.iter().collect();
+
+        assert_eq!(found.len(), 8 * 5, "Iterator did not yield every file");
+    }
+
+    #[test]
+    fn iter_dropped_early() {
+        let test_dir = make_tree("dropped", 8, 5);
+        let root = test_dir.path.clone();
+
+        let finder = FinderConfig::from(&root)
+            .with_pool_size(4)
+            .build();
+
+        let first_few: Vec<FileInfo> = finder.iter().take(3).collect();
+
+        assert_eq!(first_few.len(), 3);
+    }
+
+    #[test]
+    fn ignore_files() {
+        let test_dir = TestDir::new("ignore");
+        let root = test_dir.path.clone();
+
+        let files = [
+            "target/debug/out.txt",
+            "debug.log",
+            "keep.log",
+            "anchored.txt",
+            "sub/anchored.txt",
+            "sub/debug.log",
+            "src/main.rs",
+            "src/a/b/gen_parser.rs",
+            "src/a/b/parser.rs",
+            ".git/HEAD",
+            ".git/objects/ab/cdef",
+        ];
+
+        for file in &files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).expect("Could not create test dir");
+            fs::write(&path, b"data").expect("Could not create test file");
+        }
+
+        fs::write(root.join(".gitignore"), "# comment\ntarget/\n*.log\n!keep.log\n/anchored.txt\nsrc/**/gen_*.rs\n")
+            .expect("Could not write .gitignore");
+        fs::write(root.join("sub/.findignore"), "!debug.log\n")
+            .expect("Could not write .findignore");
+
+        let finder = FinderConfig::from(&root)
+            .with_find_only(FindOnly::Files)
+            .with_ignore_files(true)
+            .build();
+
+        let mut found: Vec<String> = finder.iter()
+            .map(|file_info| file_info.path.strip_prefix(&root).unwrap().to_string_lossy().into_owned())
+            .filter(|path| !path.ends_with("ignore"))
+            .collect();
+
+        found.sort();
+
+        assert_eq!(found, vec![
+            "keep.log",
+            "src/a/b/parser.rs",
+            "src/main.rs",
+            "sub/anchored.txt",
+            "sub/debug.log",
+        ]);
+    }
+
+    #[test]
+    fn ignore_files_stop_at_repo() {
+        let test_dir = TestDir::new("ignore-repo");
+        let base = test_dir.path.clone();
+
+        let root = base.join("repo/sub");
+        fs::create_dir_all(&root).expect("Could not create test dir");
+        fs::create_dir_all(base.join("repo/.git")).expect("Could not create test dir");
+
+        // Above the repository, so it shouldn't apply
+        fs::write(base.join(".gitignore"), "*.txt\n").expect("Could not write .gitignore");
+        fs::write(base.join("repo/.gitignore"), "*.log\n").expect("Could not write .gitignore");
+
+        for file in &["a.txt", "b.log"] {
+            fs::write(root.join(file), b"data").expect("Could not create test file");
+        }
+
+        let finder = FinderConfig::from(&root)
+            .with_find_only(FindOnly::Files)
+            .with_ignore_files(true)
+            .build();
+
+        let found: Vec<String> = finder.iter()
+            .map(|file_info| file_info.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+
+        assert_eq!(found, vec!["a.txt"]);
+    }
+
+    #[test]
+    fn content_search() {
+        let test_dir = TestDir::new("content");
+        let root = test_dir.path.clone();
+
+        let text_path = root.join("text.txt");
+        let binary_path = root.join("binary.bin");
+
+        fs::write(&text_path, "one\ntwo\nneedle three\nfour\nfive\nsix\nneedle seven\n")
+            .expect("Could not create test file");
+        fs::write(&binary_path, b"needle\0\x01\x02").expect("Could not create test file");
+
+        let search = ContentSearch::new(Regex::new("needle").unwrap())
+            .with_context_lines(1);
+
+        let found = search.search_file(&text_path).expect("No matches found");
+        let binary_found = search.search_file(&binary_path);
+        let too_big = search.clone().with_max_file_size(4).search_file(&text_path);
+
+        let numbered: Vec<(usize, bool)> = found.iter()
+            .map(|line| (line.line_number, line.is_context))
+            .collect();
+
+        assert_eq!(numbered, vec![(2, true), (3, false), (4, true), (6, true), (7, false)]);
+        assert_eq!(found[1].text, "needle three");
+        assert!(binary_found.is_none(), "Binary file should have been skipped");
+        assert!(too_big.is_none(), "File over the max size should have been skipped");
+    }
+
+    #[test]
+    fn content_search_long_lines() {
+        let test_dir = TestDir::new("content-long");
+        let root = test_dir.path.clone();
+
+        let long_path = root.join("long.txt");
+        let late_binary_path = root.join("late_binary.txt");
+
+        let mut long_text = "a".repeat(200_000);
+        long_text.push_str("needle past the cap\nneedle\n");
+        fs::write(&long_path, long_text).expect("Could not create test file");
+
+        let mut late_binary = "needle\n".repeat(10_000).into_bytes();
+        late_binary.push(0);
+        fs::write(&late_binary_path, late_binary).expect("Could not create test file");
+
+        let search = ContentSearch::new(Regex::new("needle").unwrap());
+        let found = search.search_file(&long_path).expect("No matches found");
+        let late_binary_found = search.search_file(&late_binary_path);
+
+        assert_eq!(found, vec![MatchedLine {line_number: 2, text: "needle".to_string(), is_context: false}]);
+        assert!(late_binary_found.is_none(), "Binary file should have been skipped");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follow_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let test_dir = TestDir::new("symlinks");
+        let root = test_dir.path.clone();
+        fs::create_dir_all(root.join("a")).expect("Could not create test dir");
+        fs::write(root.join("a/file.txt"), b"data").expect("Could not create test file");
+
+        symlink("..", root.join("a/loop")).expect("Could not create symlink");
+        symlink("a", root.join("b")).expect("Could not create symlink");
+        symlink("missing", root.join("dead")).expect("Could not create symlink");
+
+        let search = |follow_symlinks| {
+            let finder = FinderConfig::from(&root)
+                .with_find_only(FindOnly::Files)
+                .with_follow_symlinks(follow_symlinks)
+                .with_broken_links(true)
+                .build();
+
+            let mut found: Vec<(String, bool)> = finder.iter()
+                .map(|file_info| {
+                    let rel_path = file_info.path.strip_prefix(&root).unwrap().to_string_lossy().into_owned();
+                    (rel_path, file_info.broken_link)
+                })
+                .collect();
+
+            found.sort();
+            found
+        };
+
+        let followed = search(true);
+        let not_followed = search(false);
+
+        assert_eq!(followed, vec![
+            ("a/file.txt".to_string(), false),
+            ("b/file.txt".to_string(), false),
+            ("dead".to_string(), true),
+        ]);
+
+        assert_eq!(not_followed, vec![("a/file.txt".to_string(), false)]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn broken_links_filtered() {
+        use std::os::unix::fs::symlink;
+
+        let test_dir = TestDir::new("broken-links");
+        let root = test_dir.path.clone();
+        fs::create_dir_all(root.join("a/b")).expect("Could not create test dir");
+
+        for link in &["dead.jpg", "a/dead.jpg", "a/b/dead.jpg", "a/b/dead.txt"] {
+            symlink("missing", root.join(link)).expect("Could not create symlink");
+        }
+
+        let finder = FinderConfig::from(&root)
+            .with_follow_symlinks(true)
+            .with_broken_links(true)
+            .with_pattern(Pattern::ext("jpg"))
+            .with_min_depth(2)
+            .build();
+
+        let mut found: Vec<String> = finder.iter()
+            .map(|file_info| file_info.path.strip_prefix(&root).unwrap().to_string_lossy().into_owned())
+            .collect();
+
+        found.sort();
+
+        assert_eq!(found, vec!["a/b/dead.jpg", "a/dead.jpg"]);
+    }
+
+    #[test]
+    fn depth_limits() {
+        // dirN at depth 1, dirN/nested at 2, and the files at 3
+        let test_dir = make_tree("depth", 4, 2);
+        let root = test_dir.path.clone();
+
+        let count = |min_depth: Option<usize>, max_depth: Option<usize>| {
+            let mut config = FinderConfig::from(&root);
+
+            if let Some(min_depth) = min_depth {
+                config = config.with_min_depth(min_depth);
+            }
+
+            if let Some(max_depth) = max_depth {
+                config = config.with_max_depth(max_depth);
+            }
+
+            config.build().iter().count()
+        };
+
+        let all = count(None, None);
+        let top_two = count(None, Some(2));
+        let only_second = count(Some(2), Some(2));
+        let only_files = count(Some(3), None);
+
+        assert_eq!(all, 4 + 4 + 4 * 2);
+        assert_eq!(top_two, 4 + 4);
+        assert_eq!(only_second, 4);
+        assert_eq!(only_files, 4 * 2);
+    }
+
+    #[test]
+    fn time_filters() {
+        use std::time::{Duration, SystemTime};
+
+        let test_dir = make_tree("time", 1, 4);
+        let root = test_dir.path.clone();
+        let day = Duration::from_secs(24 * 60 * 60);
+        let now = SystemTime::now();
+
+        // Backdate half of the files by a week
+        let files: Vec<PathBuf> = FinderConfig::from(&root)
+            .with_find_only(FindOnly::Files)
+            .build()
+            .collect()
+            .into_iter()
+            .map(|file_info| file_info.path)
+            .collect();
+
+        for path in files.iter().take(2) {
+            fs::File::options().write(true).open(path).unwrap()
+                .set_modified(now - day * 7).unwrap();
+        }
+
+        let count = |patterns: Vec<Pattern>| {
+            FinderConfig::from(&root)
+                .with_find_only(FindOnly::Files)
+                .with_patterns(patterns)
+                .build()
+                .iter()
+                .count()
+        };
+
+        let recent = count(vec![Pattern::newer(TimeField::Modified, now - day)]);
+        let old = count(vec![Pattern::older(TimeField::Modified, now - day)]);
+        let between = count(vec![
+            Pattern::newer(TimeField::Modified, now - day * 8),
+            Pattern::older(TimeField::Modified, now - day * 6),
+        ]);
+
+        assert_eq!(files.len(), 4);
+        assert_eq!(recent, 2);
+        assert_eq!(old, 2);
+        assert_eq!(between, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ownership_and_permissions() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let test_dir = make_tree("perms", 1, 3);
+        let root = test_dir.path.clone();
+
+        let files: Vec<FileInfo> = FinderConfig::from(&root)
+            .with_find_only(FindOnly::Files)
+            .build()
+            .collect();
+
+        for (file_info, mode) in files.iter().zip(&[0o644, 0o664, 0o4755]) {
+            fs::set_permissions(&file_info.path, fs::Permissions::from_mode(*mode)).unwrap();
+        }
+
+        let count = |config: FinderConfig| config.with_find_only(FindOnly::Files).build().iter().count();
+
+        let exact = count(FinderConfig::from(&root).with_perm(PermMask::parse("644").unwrap()));
+        let group_writable = count(FinderConfig::from(&root).with_perm(PermMask::parse("/022").unwrap()));
+        let setuid = count(FinderConfig::from(&root).with_perm(PermMask::parse("-4000").unwrap()));
+        let all_readable = count(FinderConfig::from(&root).with_perm(PermMask::parse("-444").unwrap()));
+
+        let uid = fs::metadata(&root).unwrap().uid();
+        let owned = count(FinderConfig::from(&root).with_uid(uid));
+        let not_owned = count(FinderConfig::from(&root).with_uid(uid + 1));
+
+        assert_eq!(PermMask::parse("/0o22"), None);
+        assert_eq!(PermMask::parse("-0755"), Some(PermMask::All(0o755)));
+        assert_eq!(exact, 1);
+        assert_eq!(group_writable, 1);
+        assert_eq!(setuid, 1);
+        assert_eq!(all_readable, 3);
+        assert_eq!(owned, 3);
+        assert_eq!(not_owned, 0);
+    }
+
+    #[test]
+    fn combinators() {
+        let test_dir = TestDir::new("combinators");
+        let root = test_dir.path.clone();
+
+        let files = [
+            ("src/main.rs", "fn main() {}"),
+            ("src/lib.rs", "// TODO"),
+            ("Cargo.toml", "[package]"),
+            ("target/debug/build.rs", "fn main() {}"),
+            ("target/Cargo.toml", "[package]"),
+            ("README.md", "# TODO"),
+        ];
+
+        for (file, contents) in &files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).expect("Could not create test dir");
+            fs::write(&path, contents).expect("Could not create test file");
+        }
+
+        let find = |pattern: Pattern| {
+            let mut found: Vec<String> = FinderConfig::from(&root)
+                .with_pattern(pattern)
+                .build()
+                .collect()
+                .into_iter()
+                .map(|file_info| file_info.path.strip_prefix(&root).unwrap().to_string_lossy().into_owned())
+                .collect();
+
+            found.sort();
+            found
+        };
+
+        // (ext rs OR ext toml) AND NOT regex /target/
+        let sources = find(
+            Pattern::ext("rs").or(Pattern::ext("toml"))
+                .and(Pattern::not(Pattern::regex(Regex::new("/target/").unwrap())))
+        );
+
+        let todo = Regex::new("TODO").unwrap();
+        let not_todo = find(Pattern::ext("rs").and(Pattern::not(Pattern::contains(todo.clone()))));
+        let todo_or_toml = find(Pattern::any(vec![Pattern::contains(todo), Pattern::ext("toml")]));
+
+        assert_eq!(sources, vec!["Cargo.toml", "src/lib.rs", "src/main.rs"]);
+        assert_eq!(not_todo, vec!["src/main.rs", "target/debug/build.rs"]);
+        assert_eq!(todo_or_toml, vec!["Cargo.toml", "README.md", "src/lib.rs", "target/Cargo.toml"]);
+
+        // Name patterns get checked before anything that has to read the file
+        let sorted = Pattern::all(vec![Pattern::contains(Regex::new("x").unwrap()), Pattern::ext("rs")]);
+        match sorted {
+            Pattern::And(patterns) => assert_eq!(patterns[0].cost(), PatternCost::Name),
+            _ => panic!("Expected an And pattern"),
+        }
+    }
+
+    #[test]
+    fn fuzzy_ranking() {
+        let test_dir = TestDir::new("fuzzy");
+        let root = test_dir.path.clone();
+
+        for file in &["config/other.txt", "src/config.rs", "src/cfg_loader.rs", "notes.txt"] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).expect("Could not create test dir");
+            fs::write(&path, b"").expect("Could not create test file");
+        }
+
+        let ranked = |limit: Option<usize>| -> Vec<String> {
+            let config = FinderConfig::from(&root)
+                .with_find_only(FindOnly::Files)
+                .with_pattern(Pattern::fuzzy("config", Some(-1000)))
+                .with_order_by(OrderBy::Score, true);
+
+            let config = match limit {
+                Some(limit) => config.with_limit(limit),
+                None => config,
+            };
+
+            config.build()
+                .collect()
+                .into_iter()
+                .map(|file_info| file_info.path.strip_prefix(&root).unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+
+        let all = ranked(None);
+        let top = ranked(Some(1));
+        let none = ranked(Some(0));
+
+        // Without an order, the limit applies as the results stream in
+        let streamed = FinderConfig::from(&root).with_find_only(FindOnly::Files).with_limit(2).build().iter().count();
+
+        let path = root.join("src/config.rs");
+        let (_, positions) = fuzzy_match_path(&path, "config").unwrap();
+        let name_start = path.to_str().unwrap().chars().count() - "config.rs".len();
+
+        // Matching within the file name beats matching a directory
+        assert_eq!(all[0], "src/config.rs");
+        assert!(all.iter().position(|name| name == "config/other.txt") > all.iter().position(|name| name == "src/cfg_loader.rs"));
+        assert!(!all.contains(&"notes.txt".to_string()));
+        assert_eq!(top, vec!["src/config.rs"]);
+        assert!(none.is_empty());
+        assert_eq!(streamed, 2);
+        assert_eq!(positions, (name_start..name_start + 6).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn pseudo_filesystems() {
+        use std::path::Path;
+
+        let kind = |path: &str| {
+            let meta = fs::metadata(path).unwrap();
+            filesystem::fs_kind(Path::new(path), filesystem::dev(&meta))
+        };
+
+        assert_eq!(kind("/proc"), FsKind::Pseudo);
+        assert_eq!(kind(env::temp_dir().to_str().unwrap()), FsKind::Local);
+
+        // Going from / into /proc, which is always a separate mount
+        let root_dev = filesystem::dev(&fs::metadata("/").unwrap());
+        let proc_meta = fs::metadata("/proc").unwrap();
+        let enters_proc = |config: FinderConfig| config.can_enter_dir(Path::new("/proc"), &proc_meta, root_dev);
+
+        assert!(!enters_proc(FinderConfig::new()));
+        assert!(enters_proc(FinderConfig::new().with_pseudo_fs(true)));
+        assert!(!enters_proc(FinderConfig::new().with_pseudo_fs(true).with_one_file_system(true)));
+
+        // Staying on the same filesystem never needs a lookup
+        let self_meta = fs::metadata("/proc/self").unwrap();
+        assert!(FinderConfig::new().with_one_file_system(true).can_enter_dir(Path::new("/proc/self"), &self_meta, filesystem::dev(&proc_meta)));
+
+        // Only the crossing into another filesystem is checked, so a root on one is searched
+        assert!(FinderConfig::from("/proc").with_max_depth(1).build().iter().next().is_some());
+    }
+
+    #[test]
+    fn excludes() {
+        let test_dir = TestDir::new("excludes");
+        let root = test_dir.path.clone();
+
+        for file in &["src/main.rs", "vendor/dep/lib.rs", "node_modules/pkg/index.js", "build/out.o", "src/gen.tmp"] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).expect("Could not create test dir");
+            fs::write(&path, b"").expect("Could not create test file");
+        }
+
+        let find = |excludes: Vec<Exclude>| {
+            let mut found: Vec<String> = FinderConfig::from(&root)
+                .with_find_only(FindOnly::Files)
+                .with_excludes(excludes)
+                .build()
+                .collect()
+                .into_iter()
+                .map(|file_info| file_info.path.strip_prefix(&root).unwrap().to_string_lossy().into_owned())
+                .collect();
+
+            found.sort();
+            found
+        };
+
+        let by_name = find(vec![Exclude::parse("vendor").unwrap(), Exclude::parse("node_modules").unwrap()]);
+        let by_glob = find(vec![Exclude::parse("*.tmp").unwrap(), Exclude::parse("b*d").unwrap()]);
+        let by_regex = find(vec![Exclude::regex(Regex::new("/(vendor|node_modules|build)$").unwrap())]);
+
+        assert_eq!(by_name, vec!["build/out.o", "src/gen.tmp", "src/main.rs"]);
+        assert_eq!(by_glob, vec!["node_modules/pkg/index.js", "src/main.rs", "vendor/dep/lib.rs"]);
+        assert_eq!(by_regex, vec!["src/gen.tmp", "src/main.rs"]);
+    }
+
+    #[test]
+    fn globs() {
+        use std::path::Path;
+
+        let matches = |glob: Glob, path: &str| glob.is_match(Path::new(path));
+
+        assert!(matches(Glob::new("*.rs").unwrap(), "/src/main.rs"));
+        assert!(!matches(Glob::new("*.rs").unwrap(), "/src/main.rsx"));
+        assert!(!matches(Glob::new("main.r").unwrap(), "/src/main.rs"));
+        assert!(matches(Glob::new("file?.txt").unwrap(), "/a/file1.txt"));
+        assert!(matches(Glob::new("file[0-3].txt").unwrap(), "/a/file2.txt"));
+        assert!(!matches(Glob::new("file[!0-3].txt").unwrap(), "/a/file2.txt"));
+        assert!(matches(Glob::new("*.{rs,toml}").unwrap(), "/a/Cargo.toml"));
+        assert!(matches(Glob::new("{foo,ba{r,z}}.c").unwrap(), "/a/baz.c"));
+        assert!(matches(Glob::new("{a.c").unwrap(), "/x/{a.c"));
+
+        // Name globs only see the file name, so the '*' can't leak into a directory
+        assert!(!matches(Glob::name("src*").unwrap(), "/src/main.rs"));
+        assert!(matches(Glob::path("*.rs").unwrap(), "/src/main.rs"));
+
+        assert!(matches(Glob::new("src/**/*.rs").unwrap(), "/project/src/a/b/c.rs"));
+        assert!(matches(Glob::new("src/**/*.rs").unwrap(), "/project/src/c.rs"));
+        assert!(!matches(Glob::new("src/*.rs").unwrap(), "/project/src/a/c.rs"));
+        assert!(!matches(Glob::new("/src/*.rs").unwrap(), "/project/src/c.rs"));
+        assert!(matches(Glob::new("/project/**").unwrap(), "/project/src/c.rs"));
+    }
+
+    #[test]
+    fn glob_syntaxes() {
+        use glob::{glob_to_regex, GlobSyntax};
+
+        let matches = |pattern: &str, syntax: GlobSyntax, path: &str| {
+            Regex::new(&glob_to_regex(pattern, syntax)).unwrap().is_match(path)
+        };
+
+        assert!(matches("*.{rs,toml}", GlobSyntax::Shell, "Cargo.toml"));
+        assert!(!matches("*.{rs,toml}", GlobSyntax::Gitignore, "Cargo.toml"));
+        assert!(matches("*.{rs,toml}", GlobSyntax::Gitignore, "a/b.{rs,toml}"));
+
+        // Without a slash an ignore rule matches at any depth, with one it's anchored
+        assert!(matches("*.log", GlobSyntax::Gitignore, "a/b/debug.log"));
+        assert!(!matches("*.log", GlobSyntax::Shell, "a/b/debug.log"));
+        assert!(matches("/debug.log", GlobSyntax::Gitignore, "debug.log"));
+        assert!(!matches("/debug.log", GlobSyntax::Gitignore, "a/debug.log"));
+        assert!(!matches("a/*.log", GlobSyntax::Gitignore, "b/a/debug.log"));
+
+        for syntax in &[GlobSyntax::Shell, GlobSyntax::Gitignore] {
+            assert!(matches("src/**/gen_*.rs", *syntax, "src/a/b/gen_parser.rs"));
+            assert!(matches("src/**/gen_*.rs", *syntax, "src/gen_parser.rs"));
+            assert!(matches("[!a-c]?.txt", *syntax, "d1.txt"));
+            assert!(!matches("[!a-c]?.txt", *syntax, "b1.txt"));
+        }
+    }
+
+    #[test]
+    fn sniff_file_types() {
+        let test_dir = TestDir::new("sniff");
+        let root = test_dir.path.clone();
+
+        let png: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0x0D];
+        let jpeg: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F'];
+
+        fs::write(root.join("IMG_0001"), png).unwrap();
+        fs::write(root.join("holiday.txt"), jpeg).unwrap();
+        fs::write(root.join("photo.png"), b"not really a png").unwrap();
+        fs::write(root.join("main.rs"), b"fn main() {}").unwrap();
+        fs::write(root.join("lib.rs"), png).unwrap();
+
+        let find_kind = |file_type: FileType, detection: TypeDetection| {
+            let mut found: Vec<String> = FinderConfig::from(&root)
+                .file_type(file_type)
+                .with_type_detection(detection)
+                .build()
+                .collect()
+                .into_iter()
+                .map(|file_info| file_info.path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect();
+
+            found.sort();
+            found
+        };
+
+        let find = |detection: TypeDetection| find_kind(FileType::Image, detection);
+
+        let by_ext = find(TypeDetection::Extension);
+        let by_sniff = find(TypeDetection::Sniff);
+        let by_either = find(TypeDetection::Either);
+        let code_by_sniff = find_kind(FileType::Code, TypeDetection::Sniff);
+
+        assert_eq!(by_ext, vec!["photo.png"]);
+        assert_eq!(by_sniff, vec!["IMG_0001", "holiday.txt", "lib.rs"]);
+        assert_eq!(by_either, vec!["IMG_0001", "holiday.txt", "lib.rs", "photo.png"]);
+        assert_eq!(code_by_sniff, vec!["main.rs"]);
+
+        assert_eq!(sniff::sniff_bytes(b"%PDF-1.7"), Some("application/pdf"));
+        assert_eq!(sniff::sniff_bytes(b"\x00\x00\x00\x18ftypmp42"), Some("video/mp4"));
+        assert_eq!(sniff::sniff_bytes("caf\u{e9}".as_bytes()), Some("text/plain"));
+        assert_eq!(sniff::sniff_bytes(&[0xC3]), Some("text/plain"));
+        assert_eq!(sniff::sniff_bytes(&[0, 1, 2, 3]), None);
+    }
+
+    #[test]
+    fn file_type_taxonomy() {
+        use std::path::Path;
+
+        let is = |file_type: &FileType, path: &str| file_type.matches_file(Path::new(path));
+
+        assert!(is(&FileType::Code, "main.rs"));
+        assert!(!is(&FileType::Code, "notes.txt"));
+        assert!(is(&FileType::Json, "data.json"));
+        assert!(!is(&FileType::Json, "notes.txt"));
+        assert!(is(&FileType::Archive, "backup.tar.gz"));
+        assert!(is(&FileType::Document, "paper.PDF"));
+        assert!(is(&FileType::Spreadsheet, "budget.xlsx"));
+        assert!(is(&FileType::Audio, "song.mp3"));
+        assert!(is(&FileType::Config, "Cargo.toml"));
+        assert!(is(&FileType::Any, "Makefile"));
+
+        assert!(is(&FileType::Mime("image/*".to_string()), "photo.jpg"));
+        assert!(is(&FileType::Mime("application/pdf".to_string()), "paper.pdf"));
+        assert!(!is(&FileType::Mime("image/png".to_string()), "photo.jpg"));
+
+        let raw = FileType::Custom(CustomType::new("raw").with_ext(".cr2").with_ext("nef"));
+        assert!(is(&raw, "IMG_0001.CR2"));
+        assert!(!is(&raw, "IMG_0001.jpg"));
+
+        assert!(matches!(FileType::from_name("Images"), Some(FileType::Image)));
+        assert!(matches!(FileType::from_name("video/*"), Some(FileType::Mime(_))));
+        assert!(FileType::from_name("nonsense").is_none());
+
+        assert_eq!(FileType::Spreadsheet.to_string(), "spreadsheet");
+        assert_eq!(FileType::Mime("video/*".to_string()).to_string(), "video/*");
+        assert_eq!(raw.to_string(), "raw");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn find_dupes() {
+        let test_dir = TestDir::new("dupes");
+        let root = test_dir.path.clone();
+        fs::create_dir_all(root.join("sub")).expect("Could not create test dir");
+
+        // Big enough that the partial hash doesn't cover the middle of the file
+        let big: Vec<u8> = (0..64 * 1024).map(|idx| (idx % 251) as u8).collect();
+        let mut big_changed = big.clone();
+        big_changed[32 * 1024] ^= 0xFF;
+
+        fs::write(root.join("big1"), &big).unwrap();
+        fs::write(root.join("sub/big2"), &big).unwrap();
+        fs::write(root.join("big_changed"), &big_changed).unwrap();
+        fs::write(root.join("small1"), b"same").unwrap();
+        fs::write(root.join("small2"), b"same").unwrap();
+        fs::write(root.join("small3"), b"diff").unwrap();
+        fs::write(root.join("empty1"), b"").unwrap();
+        fs::write(root.join("empty2"), b"").unwrap();
+        fs::hard_link(root.join("small3"), root.join("small3_link")).unwrap();
+
+        let names = |group: &DupeGroup| -> Vec<String> {
+            group.files.iter()
+                .map(|file_info| file_info.path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+
+        let groups = FinderConfig::from(&root).build().find_dupes(&DupeFinder::new());
+        let big_only = FinderConfig::from(&root).build().find_dupes(&DupeFinder::new().with_min_size(1024));
+        let with_links = FinderConfig::from(&root).build().find_dupes(&DupeFinder::new().with_hard_links(true));
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(names(&groups[0]), vec!["big1", "big2"]);
+        assert_eq!(groups[0].wasted(), big.len() as u64);
+        assert_eq!(names(&groups[1]), vec!["small1", "small2"]);
+
+        assert_eq!(big_only.len(), 1);
+
+        assert_eq!(with_links.len(), 3);
+        assert!(with_links.iter().any(|group| names(group) == vec!["small3", "small3_link"]));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn disk_usage() {
+        let test_dir = TestDir::new("du");
+        let root = test_dir.path.clone();
+        fs::create_dir_all(root.join("a/deep")).expect("Could not create test dir");
+        fs::create_dir_all(root.join("b")).expect("Could not create test dir");
+
+        fs::write(root.join("top"), vec![0; 100]).unwrap();
+        fs::write(root.join("a/one"), vec![0; 1000]).unwrap();
+        fs::write(root.join("a/deep/two"), vec![0; 2000]).unwrap();
+        fs::write(root.join("b/three"), vec![0; 3000]).unwrap();
+        fs::hard_link(root.join("b/three"), root.join("b/three_link")).unwrap();
+
+        let apparent = DiskUsage::new().with_size_mode(SizeMode::Apparent);
+        let dir_len = |path: PathBuf| fs::metadata(path).unwrap().len();
+
+        let all = FinderConfig::from(&root).build().disk_usage(&apparent);
+        let shallow = FinderConfig::from(&root).build().disk_usage(&apparent.clone().with_max_depth(1));
+        let blocks = FinderConfig::from(&root).build().disk_usage(&DiskUsage::new());
+        let measured = apparent.measure(&root, FinderConfig::from(&root).build());
+
+        let b_len = dir_len(root.join("b"));
+        let dirs_len = dir_len(root.clone()) + dir_len(root.join("a")) + dir_len(root.join("a/deep")) + b_len;
+        let find = |usage: &[DirUsage], rel: &str| usage.iter().find(|dir_usage| dir_usage.path == root.join(rel)).map(|dir_usage| dir_usage.totals);
+
+        let root_totals = find(&all, "").unwrap();
+        let a_totals = find(&all, "a").unwrap();
+        let b_totals = find(&all, "b").unwrap();
+
+        // The hard link only counts once
+        assert_eq!(root_totals.size, 6100 + dirs_len);
+        assert_eq!(root_totals.inodes, 8);
+        assert_eq!(a_totals.inodes, 4);
+        assert_eq!(b_totals.size, 3000 + b_len);
+        assert_eq!(b_totals.inodes, 2);
+
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[0].path, root);
+        assert_eq!(shallow.len(), 3);
+        assert!(shallow.iter().all(|dir_usage| dir_usage.depth <= 1));
+        assert_eq!(find(&shallow, "").unwrap(), root_totals);
+
+        assert_eq!(blocks[0].totals.inodes, 8);
+
+        // Adding up plain results on the calling thread comes to the same thing
+        let totals = |usage: &[DirUsage]| usage.iter().map(|dir_usage| (dir_usage.path.clone(), dir_usage.totals)).collect::<Vec<_>>();
+        assert_eq!(totals(&measured), totals(&all));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn watch() {
+        let test_dir = TestDir::new("watch");
+        let root = test_dir.path.clone();
+        fs::write(root.join("before.txt"), b"").unwrap();
+        fs::write(root.join("before.rs"), b"").unwrap();
+        std::os::unix::fs::symlink(root.join("missing"), root.join("dangling.txt")).unwrap();
+
+        let mut watcher = FinderConfig::from(&root)
+            .with_pattern(Pattern::ext("txt"))
+            .with_follow_symlinks(true)
+            .with_broken_links(true)
+            .build()
+            .watch()
+            .expect("Could not start watching");
+
+        let paths = |events: Vec<WatchEvent>| -> Vec<(bool, String)> {
+            let mut paths: Vec<(bool, String)> = events.into_iter()
+                .map(|event| match event {
+                    WatchEvent::Matched(file_info) => (true, file_info.path.clone()),
+                    WatchEvent::Unmatched(path) => (false, path),
+                })
+                .map(|(matched, path)| (matched, path.strip_prefix(&root).unwrap().to_string_lossy().into_owned()))
+                .collect();
+
+            paths.sort();
+            paths
+        };
+
+        let initial = paths(watcher.start());
+
+        // New directories get watched, along with anything already in them
+        fs::create_dir_all(root.join("new/deeper")).unwrap();
+        fs::write(root.join("new/deeper/early.txt"), b"").unwrap();
+        let created_dirs = paths(watcher.wait().unwrap());
+
+        fs::write(root.join("new/deeper/late.txt"), b"data").unwrap();
+        fs::write(root.join("new/ignored.rs"), b"data").unwrap();
+        let created_files = paths(watcher.wait().unwrap());
+
+        fs::rename(root.join("before.txt"), root.join("renamed.rs")).unwrap();
+        let renamed = paths(watcher.wait().unwrap());
+
+        fs::remove_dir_all(root.join("new")).unwrap();
+        let removed = paths(watcher.wait().unwrap());
+
+        std::os::unix::fs::symlink(root.join("missing"), root.join("new_dangling.txt")).unwrap();
+        let new_link = paths(watcher.wait().unwrap());
+
+        assert_eq!(initial, vec![(true, "before.txt".to_string()), (true, "dangling.txt".to_string())]);
+        assert_eq!(created_dirs, vec![(true, "new/deeper/early.txt".to_string())]);
+        assert_eq!(created_files, vec![(true, "new/deeper/late.txt".to_string())]);
+        assert_eq!(renamed, vec![(false, "before.txt".to_string())]);
+        assert_eq!(removed, vec![(false, "new/deeper/early.txt".to_string()), (false, "new/deeper/late.txt".to_string())]);
+        assert_eq!(new_link, vec![(true, "new_dangling.txt".to_string())]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn index() {
+        let test_dir = TestDir::new("index");
+        let root = test_dir.path.clone();
+        fs::create_dir_all(root.join("photos/2020")).expect("Could not create test dir");
+        fs::create_dir_all(root.join("docs")).expect("Could not create test dir");
+
+        fs::write(root.join("photos/2020/a.jpg"), vec![0; 2000]).unwrap();
+        fs::write(root.join("photos/b.jpg"), vec![0; 10]).unwrap();
+        fs::write(root.join("docs/notes.txt"), b"notes").unwrap();
+
+        let index_path = root.join("index.db");
+        let excluded_path = root.join("index-excluded.db");
+        let root = fs::canonicalize(&root).unwrap();
+
+        let names_in = |index_path: &PathBuf, config: FinderConfig, under: &PathBuf| -> Vec<String> {
+            let mut names: Vec<String> = Index::search(index_path, &config, under).unwrap()
+                .map(|found| found.unwrap().path.strip_prefix(&root).unwrap().to_string_lossy().into_owned())
+                .filter(|name| !name.starts_with("index"))
+                .collect();
+
+            names.sort();
+            names
+        };
+        let names = |config: FinderConfig, under: &PathBuf| names_in(&index_path, config, under);
+
+        Index::build(&root, &FinderConfig::new()).unwrap().save(&index_path).unwrap();
+
+        // Excluded directories never make it into the index, or get read
+        Index::build(&root, &FinderConfig::new().with_exclude(Exclude::name("2020"))).unwrap().save(&excluded_path).unwrap();
+        let without_excluded = names_in(&excluded_path, FinderConfig::new(), &root);
+
+        let jpgs = names(FinderConfig::new().with_pattern(Pattern::ext("jpg")), &root);
+        let big_jpgs = names(FinderConfig::new().with_pattern(Pattern::ext("jpg")).with_min_size(1000), &root);
+        let shallow = names(FinderConfig::new().with_max_depth(1).with_find_only(FindOnly::Files), &root.join("photos"));
+
+        let biggest_config = FinderConfig::new().with_pattern(Pattern::ext("jpg")).with_limit(1);
+        let biggest_config = biggest_config.with_order_by(OrderBy::Size, true);
+        let biggest: Vec<(String, u64)> = Index::arrange(&biggest_config, Index::search(&index_path, &biggest_config, &root).unwrap().map(Result::unwrap))
+            .into_iter()
+            .map(|found| (found.path.file_name().unwrap().to_string_lossy().into_owned(), found.stat.size))
+            .collect();
+
+        // The index is all a search goes by, so nothing changes until it's updated
+        fs::write(root.join("photos/2020/c.jpg"), b"").unwrap();
+        fs::remove_file(root.join("photos/b.jpg")).unwrap();
+        let before_update = names(FinderConfig::new().with_pattern(Pattern::ext("jpg")), &root);
+
+        Index::load(&index_path).unwrap().update(&FinderConfig::new()).save(&index_path).unwrap();
+        let after_update = names(FinderConfig::new().with_pattern(Pattern::ext("jpg")), &root);
+
+        // Anything that needs the files themselves can't be answered from it
+        let by_owner = Index::unsupported(&FinderConfig::new().with_pattern(!Pattern::uid(0)));
+
+        assert_eq!(jpgs, vec!["photos/2020/a.jpg", "photos/b.jpg"]);
+        assert_eq!(without_excluded, vec!["docs", "docs/notes.txt", "photos", "photos/b.jpg"]);
+        assert_eq!(big_jpgs, vec!["photos/2020/a.jpg"]);
+        assert_eq!(shallow, vec!["photos/b.jpg"]);
+        assert_eq!(biggest, vec![("a.jpg".to_string(), 2000)]);
+        assert_eq!(before_update, vec!["photos/2020/a.jpg", "photos/b.jpg"]);
+        assert_eq!(after_update, vec!["photos/2020/a.jpg", "photos/2020/c.jpg"]);
+        assert_eq!(by_owner, Some("owners"));
+        assert_eq!(Index::unsupported(&FinderConfig::new().with_pattern(Pattern::ext("jpg"))), None);
+    }
+}
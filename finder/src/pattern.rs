@@ -1,5 +1,14 @@
 
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, Metadata};
+use std::ops::Not;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
 
 
 use lazy_static::lazy_static;
@@ -11,8 +20,145 @@ use regex::{Regex, Captures};
 
 use sublime_fuzzy::FuzzySearch;
 
+use super::content::ContentSearch;
+use super::glob::Glob;
+use super::users;
+use super::walk::FileInfo;
+
 static DEFAULT_FUZZY_THRESHOLD: isize = 1;
 
+// Added to the score when the query matches within the file name alone
+static BASENAME_BONUS: isize = 100;
+
+
+/// Fuzzy matches `fuzzy` against a path. The whole query matching within the file name
+/// beats it being spread across directories, so those matches get a bonus. Returns the
+/// score along with the (char) indexes of each matched character in the full path.
+pub fn fuzzy_match_path(path: &Path, fuzzy: &str) -> Option<(isize, Vec<usize>)> {
+    let path_str = path.to_str()?;
+    let (score, positions) = fuzzy_match(fuzzy, path_str)?;
+
+    // The path is only matched once, so the bonus goes by where that match landed
+    let name_len = path.file_name().and_then(|name| name.to_str()).map(|name| name.chars().count()).unwrap_or(0);
+    let name_start = path_str.chars().count() - name_len;
+
+    match name_len > 0 && positions.iter().all(|pos| *pos >= name_start) {
+        true => Some((score + BASENAME_BONUS, positions)),
+        false => Some((score, positions)),
+    }
+}
+
+// Scores how well `fuzzy` matches the path, or isize::MIN if it doesn't match at all
+pub(crate) fn fuzzy_score_path(path: &Path, fuzzy: &str) -> isize {
+    fuzzy_match_path(path, fuzzy)
+        .map(|(score, _)| score)
+        .unwrap_or(isize::MIN)
+}
+
+/// Fuzzy matches `fuzzy` against `target`, ignoring case. Returns the score along with the
+/// (char, not byte) indexes of each matched character, for highlighting.
+pub fn fuzzy_match(fuzzy: &str, target: &str) -> Option<(isize, Vec<usize>)> {
+    FuzzySearch::new(fuzzy, target)
+        .case_insensitive()
+        .best_match()
+        .map(|found| (found.score(), found.matched_indices().copied().collect()))
+}
+
+
+
+static CODE_EXTS: &[&str] = &[
+    "rs", "c", "h", "cc", "cpp", "cxx", "hpp", "hh", "m", "mm", "go", "py", "pyi", "rb", "php",
+    "js", "mjs", "cjs", "jsx", "ts", "tsx", "java", "kt", "kts", "scala", "swift", "cs", "fs",
+    "lua", "pl", "pm", "r", "hs", "ml", "mli", "ex", "exs", "erl", "clj", "dart", "zig", "nim",
+    "sh", "bash", "zsh", "fish", "ps1", "sql", "vue", "svelte", "asm", "s",
+];
+
+static JSON_EXTS: &[&str] = &["json", "jsonl", "ndjson", "geojson", "json5"];
+
+static ARCHIVE_EXTS: &[&str] = &[
+    "zip", "tar", "gz", "tgz", "bz2", "tbz2", "xz", "txz", "zst", "lz4", "lzma", "7z", "rar", "cab",
+];
+
+static DOCUMENT_EXTS: &[&str] = &["pdf", "doc", "docx", "odt", "rtf", "epub", "pages", "tex"];
+
+static SPREADSHEET_EXTS: &[&str] = &["xls", "xlsx", "xlsm", "ods", "numbers", "csv", "tsv"];
+
+static FONT_EXTS: &[&str] = &["ttf", "otf", "woff", "woff2", "eot", "pfb"];
+
+static EXECUTABLE_EXTS: &[&str] = &["exe", "dll", "so", "dylib", "bin", "appimage", "msi", "com"];
+
+static CONFIG_EXTS: &[&str] = &["toml", "yaml", "yml", "ini", "cfg", "conf", "env", "properties", "plist"];
+
+static ARCHIVE_MIMES: &[&str] = &[
+    "application/zip", "application/gzip", "application/x-tar", "application/x-bzip2",
+    "application/x-xz", "application/zstd", "application/x-7z-compressed",
+    "application/vnd.rar", "application/x-rar-compressed",
+];
+
+static DOCUMENT_MIMES: &[&str] = &[
+    "application/pdf", "application/msword", "application/rtf", "application/epub+zip",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    "application/vnd.oasis.opendocument.text",
+];
+
+static SPREADSHEET_MIMES: &[&str] = &[
+    "text/csv", "text/tab-separated-values", "application/vnd.ms-excel",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    "application/vnd.oasis.opendocument.spreadsheet",
+];
+
+static EXECUTABLE_MIMES: &[&str] = &[
+    "application/x-executable", "application/x-sharedlib", "application/x-msdownload",
+    "application/x-mach-binary", "application/vnd.microsoft.portable-executable",
+];
+
+static CONFIG_MIMES: &[&str] = &["application/toml", "application/yaml", "application/x-yaml", "text/yaml"];
+
+
+/// Checks a MIME type against a pattern where either half can be '*', like "image/*".
+fn mime_pattern_matches(pattern: &str, det_mime: &mime::Mime) -> bool {
+    let (type_pattern, subtype_pattern) = match pattern.find('/') {
+        Some(idx) => (&pattern[..idx], &pattern[idx + 1..]),
+        None => (pattern, "*"),
+    };
+
+    (type_pattern == "*" || det_mime.type_().as_str().eq_ignore_ascii_case(type_pattern))
+        && (subtype_pattern == "*" || det_mime.subtype().as_str().eq_ignore_ascii_case(subtype_pattern))
+}
+
+fn has_ext(path: &Path, exts: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| exts.iter().any(|known| known.eq_ignore_ascii_case(ext)))
+        .unwrap_or(false)
+}
+
+
+/// A user defined file type, matched by extension or MIME type.
+#[derive(Debug, Clone, Default)]
+pub struct CustomType {
+    pub name: String,
+    // Without the leading '.'
+    pub exts: Vec<String>,
+    // Can use '*' wildcards, like "image/*"
+    pub mimes: Vec<String>,
+}
+
+impl CustomType {
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        Self {name: name.into(), ..Default::default()}
+    }
+
+    pub fn with_ext<T: Into<String>>(mut self, ext: T) -> Self {
+        self.exts.push(ext.into().trim().trim_start_matches('.').to_string());
+        self
+    }
+
+    pub fn with_mime<T: Into<String>>(mut self, mime: T) -> Self {
+        self.mimes.push(mime.into());
+        self
+    }
+}
 
 
 #[derive(Debug, Clone)]
@@ -20,45 +166,275 @@ pub enum FileType {
     Any,
     Image,
     Video,
+    Audio,
+    // Anything with a text/* MIME type
     Text,
     Code,
     Json,
+    Archive,
+    Document,
+    Spreadsheet,
+    Font,
+    Executable,
+    Config,
+    // A MIME type, where either half can be a '*' (ex. "image/*")
+    Mime(String),
+    Custom(CustomType),
 }
 
 
 impl FileType {
+    /// Looks up one of the built in types by name, or a MIME type if there's a '/' in it.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+
+        if name.contains('/') {
+            return Some(FileType::Mime(name));
+        }
+
+        let file_type = match name.as_str() {
+            "any" | "all" => FileType::Any,
+            "image" | "images" | "img" | "imgs" => FileType::Image,
+            "video" | "videos" | "vid" | "vids" => FileType::Video,
+            "audio" | "music" | "sound" => FileType::Audio,
+            "text" | "txt" => FileType::Text,
+            "code" | "source" | "src" => FileType::Code,
+            "json" => FileType::Json,
+            "archive" | "archives" | "compressed" => FileType::Archive,
+            "document" | "documents" | "doc" | "docs" => FileType::Document,
+            "spreadsheet" | "spreadsheets" | "sheet" | "sheets" => FileType::Spreadsheet,
+            "font" | "fonts" => FileType::Font,
+            "executable" | "executables" | "exe" | "bin" => FileType::Executable,
+            "config" | "conf" | "cfg" => FileType::Config,
+            _ => return None,
+        };
+
+        Some(file_type)
+    }
+
     pub fn to_pattern(&self) -> Pattern {
         self.clone().into()
     }
 
+    // Extensions that belong to this type, whatever MIME type they'd be guessed as
+    fn exts(&self) -> &'static [&'static str] {
+        match &self {
+            FileType::Code => CODE_EXTS,
+            FileType::Json => JSON_EXTS,
+            FileType::Archive => ARCHIVE_EXTS,
+            FileType::Document => DOCUMENT_EXTS,
+            FileType::Spreadsheet => SPREADSHEET_EXTS,
+            FileType::Font => FONT_EXTS,
+            FileType::Executable => EXECUTABLE_EXTS,
+            FileType::Config => CONFIG_EXTS,
+            _ => &[],
+        }
+    }
+
     pub fn matches_mime(&self, det_mime: &mime::Mime) -> bool {
         let name = det_mime.type_();
+        let essence = det_mime.essence_str();
 
         match &self {
             FileType::Any => true,
-            FileType::Image => name == "image",
-            FileType::Video => name == "video",
-            FileType::Text | FileType::Code | FileType::Json => name == "text",
-            _ => false,
+            FileType::Image => name == mime::IMAGE,
+            FileType::Video => name == mime::VIDEO,
+            FileType::Audio => name == mime::AUDIO,
+            FileType::Text => name == mime::TEXT,
+            // There's no MIME type for source code in general, so this only goes by extension
+            // (see `matches_sniffed`).
+            FileType::Code => false,
+            FileType::Json => det_mime.subtype() == mime::JSON || det_mime.suffix() == Some(mime::JSON),
+            FileType::Archive => ARCHIVE_MIMES.contains(&essence),
+            FileType::Document => DOCUMENT_MIMES.contains(&essence),
+            FileType::Spreadsheet => SPREADSHEET_MIMES.contains(&essence),
+            FileType::Font => name == mime::FONT || essence.starts_with("application/font-")
+                || essence.starts_with("application/x-font-"),
+            FileType::Executable => EXECUTABLE_MIMES.contains(&essence),
+            FileType::Config => CONFIG_MIMES.contains(&essence),
+            FileType::Mime(pattern) => mime_pattern_matches(pattern, det_mime),
+            FileType::Custom(custom) => custom.mimes.iter().any(|pattern| mime_pattern_matches(pattern, det_mime)),
+        }
+    }
+
+    /// Matches a MIME type sniffed from the file's content. Sniffing can't tell code, JSON or
+    /// config files from any other text, so plain text falls back on the extension.
+    pub fn matches_sniffed(&self, path: &Path, det_mime: &mime::Mime) -> bool {
+        match *det_mime == mime::TEXT_PLAIN {
+            true => self.matches_mime(det_mime) || has_ext(path, self.exts()),
+            false => self.matches_mime(det_mime),
         }
     }
 
+    /// Matches by extension, without reading the file.
     pub fn matches_file(&self, path: &Path) -> bool {
-        match mime_guess::from_path(path).first() {
-            Some(detected_mime) => self.matches_mime(&detected_mime),
-            _ => false,
+        let known_ext = match &self {
+            FileType::Any => return true,
+            FileType::Custom(custom) => {
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(|ext| custom.exts.iter().any(|known| known.eq_ignore_ascii_case(ext)))
+                    .unwrap_or(false)
+            },
+            _ => has_ext(path, self.exts()),
+        };
+
+        known_ext || mime_guess::from_path(path).iter().any(|guess| self.matches_mime(&guess))
+    }
+}
+
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FileType::Any => "any",
+            FileType::Image => "image",
+            FileType::Video => "video",
+            FileType::Audio => "audio",
+            FileType::Text => "text",
+            FileType::Code => "code",
+            FileType::Json => "json",
+            FileType::Archive => "archive",
+            FileType::Document => "document",
+            FileType::Spreadsheet => "spreadsheet",
+            FileType::Font => "font",
+            FileType::Executable => "executable",
+            FileType::Config => "config",
+            FileType::Mime(mime) => mime,
+            FileType::Custom(custom) => &custom.name,
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeField {
+    Modified,
+    Accessed,
+    // When the inode (permissions, owner, links, etc) last changed
+    Changed,
+    // Birth time, which not every filesystem keeps track of
+    Created,
+}
+
+impl TimeField {
+    pub fn of(&self, meta: &Metadata) -> Option<SystemTime> {
+        match self {
+            TimeField::Modified => meta.modified().ok(),
+            TimeField::Accessed => meta.accessed().ok(),
+            TimeField::Changed => changed_time(meta),
+            TimeField::Created => meta.created().ok(),
         }
     }
 }
 
+#[cfg(unix)]
+fn changed_time(meta: &Metadata) -> Option<SystemTime> {
+    let secs = meta.ctime();
+    let nanos = Duration::from_nanos(meta.ctime_nsec() as u64);
+
+    match secs >= 0 {
+        true => Some(UNIX_EPOCH + Duration::from_secs(secs as u64) + nanos),
+        false => Some(UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()) + nanos),
+    }
+}
+
+#[cfg(not(unix))]
+fn changed_time(_meta: &Metadata) -> Option<SystemTime> {
+    None
+}
+
+
+
+/// Checks the permission bits of a file, like the different forms of GNU find's `-perm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermMask {
+    // The mode is exactly this ("644")
+    Exact(u32),
+    // At least one of these bits is set ("/022")
+    Any(u32),
+    // Every one of these bits is set ("-022")
+    All(u32),
+}
+
+impl PermMask {
+    /// Parses an octal mode, with an optional '/' or '-' prefix for `Any` or `All`.
+    pub fn parse(mask_str: &str) -> Option<Self> {
+        let mask_str = mask_str.trim();
+
+        let (variant, digits): (fn(u32) -> PermMask, &str) = match mask_str.chars().next()? {
+            '/' => (PermMask::Any, &mask_str[1..]),
+            '-' => (PermMask::All, &mask_str[1..]),
+            _ => (PermMask::Exact, mask_str),
+        };
+
+        match u32::from_str_radix(digits, 8) {
+            Ok(bits) if bits <= 0o7777 => Some(variant(bits)),
+            _ => None,
+        }
+    }
+
+    pub fn matches_mode(&self, mode: u32) -> bool {
+        let mode = mode & 0o7777;
+
+        match *self {
+            PermMask::Exact(bits) => mode == bits,
+            // Like GNU find, "/000" matches everything
+            PermMask::Any(bits) => bits == 0 || mode & bits != 0,
+            PermMask::All(bits) => mode & bits == bits,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn owner_ids(meta: &Metadata) -> Option<(u32, u32, u32)> {
+    Some((meta.uid(), meta.gid(), meta.mode()))
+}
+
+#[cfg(not(unix))]
+fn owner_ids(_meta: &Metadata) -> Option<(u32, u32, u32)> {
+    None
+}
+
+
+
+/// Roughly how expensive a pattern is to check, so the cheap ones can rule a file out first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PatternCost {
+    // Only needs the path
+    Name,
+    // Needs the file's metadata, which the walker already has
+    Metadata,
+    // Has to open and read the file
+    Content,
+}
+
 
 
 #[derive(Debug, Clone)]
 pub enum Pattern {
     Ext(String),
     Regex(Regex),
+    Glob(Glob),
     Fuzzy(String, isize),
-    FileType(FileType)
+    FileType(FileType),
+    Contains(ContentSearch),
+    // The given time field is after/before the timestamp
+    Newer(TimeField, SystemTime),
+    Older(TimeField, SystemTime),
+    Uid(u32),
+    Gid(u32),
+    // Owned by a uid/gid that isn't in this set, like a user that's since been removed
+    NoUser(Arc<HashSet<u32>>),
+    NoGroup(Arc<HashSet<u32>>),
+    Perm(PermMask),
+    // Every pattern has to match
+    And(Vec<Pattern>),
+    // At least one pattern has to match
+    Or(Vec<Pattern>),
+    Not(Box<Pattern>),
 }
 
 impl Pattern {
@@ -66,7 +442,9 @@ impl Pattern {
     where
         T: Into<String>
     {
-        Pattern::Ext(ext.into())
+        // Store the extension without the leading '.', so "rs" and ".rs" mean the same thing
+        let ext: String = ext.into();
+        Pattern::Ext(ext.trim().trim_start_matches('.').to_string())
     }
 
     pub fn regex<T>(regex: T) -> Self
@@ -76,6 +454,10 @@ impl Pattern {
         Pattern::Regex(regex.into())
     }
 
+    pub fn glob(glob: Glob) -> Self {
+        Pattern::Glob(glob)
+    }
+
     pub fn fuzzy<T>(fuzzy: T, thresh: Option<isize>) -> Self
     where
         T: Into<String>
@@ -87,19 +469,257 @@ impl Pattern {
         Pattern::FileType(f_type)
     }
 
+    pub fn newer(field: TimeField, time: SystemTime) -> Self {
+        Pattern::Newer(field, time)
+    }
+
+    pub fn older(field: TimeField, time: SystemTime) -> Self {
+        Pattern::Older(field, time)
+    }
+
+    pub fn uid(uid: u32) -> Self {
+        Pattern::Uid(uid)
+    }
+
+    pub fn gid(gid: u32) -> Self {
+        Pattern::Gid(gid)
+    }
+
+    /// Files owned by the user with this name (or uid), or None if there's no such user.
+    pub fn user(user: &str) -> Option<Self> {
+        users::uid_for_user(user).map(Pattern::Uid)
+    }
+
+    /// Files owned by the group with this name (or gid), or None if there's no such group.
+    pub fn group(group: &str) -> Option<Self> {
+        users::gid_for_group(group).map(Pattern::Gid)
+    }
+
+    /// Files whose owner isn't listed in /etc/passwd.
+    pub fn no_user() -> Self {
+        Pattern::NoUser(Arc::new(users::known_uids()))
+    }
+
+    /// Files whose group isn't listed in /etc/group.
+    pub fn no_group() -> Self {
+        Pattern::NoGroup(Arc::new(users::known_gids()))
+    }
+
+    pub fn perm(mask: PermMask) -> Self {
+        Pattern::Perm(mask)
+    }
+
+    /// Matches if every one of the patterns does. They're reordered so the cheapest get
+    /// checked first.
+    pub fn all<V>(patterns: V) -> Self
+    where
+        V: Into<Vec<Pattern>>
+    {
+        Pattern::And(sorted_by_cost(patterns.into()))
+    }
+
+    /// Matches if any one of the patterns does. They're reordered so the cheapest get
+    /// checked first.
+    pub fn any<V>(patterns: V) -> Self
+    where
+        V: Into<Vec<Pattern>>
+    {
+        Pattern::Or(sorted_by_cost(patterns.into()))
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(pattern: Pattern) -> Self {
+        Pattern::Not(Box::new(pattern))
+    }
+
+    /// Combines this pattern with another, where both have to match. Chaining `and`s builds
+    /// up a single `And`, rather than nesting them.
+    pub fn and(self, other: Pattern) -> Self {
+        match self {
+            Pattern::And(mut patterns) => {
+                patterns.push(other);
+                Pattern::all(patterns)
+            },
+            pattern => Pattern::all(vec![pattern, other]),
+        }
+    }
+
+    /// Combines this pattern with another, where either can match. Chaining `or`s builds
+    /// up a single `Or`, rather than nesting them.
+    pub fn or(self, other: Pattern) -> Self {
+        match self {
+            Pattern::Or(mut patterns) => {
+                patterns.push(other);
+                Pattern::any(patterns)
+            },
+            pattern => Pattern::any(vec![pattern, other]),
+        }
+    }
+
+    pub fn contains<T>(search: T) -> Self
+    where
+        T: Into<ContentSearch>
+    {
+        Pattern::Contains(search.into())
+    }
+
 
     pub fn matches(&self, path: &Path) -> bool {
         match &self {
-            Pattern::Ext(ext) => path.ends_with(ext),
+            Pattern::Ext(ext) => {
+                path.extension()
+                    .and_then(|path_ext| path_ext.to_str())
+                    .map(|path_ext| path_ext.eq_ignore_ascii_case(ext))
+                    .unwrap_or(false)
+            },
             Pattern::Regex(regex) => {
                 path.to_str()
                     .map(|path_str| regex.is_match(path_str))
                     .unwrap_or(false)
             },
-            Pattern::Fuzzy(fuzzy, thresh) => fuzzy_match_path(path, fuzzy.to_string()) > *thresh,
+            Pattern::Glob(glob) => glob.is_match(path),
+            Pattern::Fuzzy(fuzzy, thresh) => fuzzy_score_path(path, fuzzy) > *thresh,
             Pattern::FileType(f_type) => f_type.matches_file(path),
+            Pattern::Contains(search) => search.search_file(path).is_some(),
+            Pattern::Newer(..) | Pattern::Older(..)
+                | Pattern::Uid(_) | Pattern::Gid(_)
+                | Pattern::NoUser(_) | Pattern::NoGroup(_)
+                | Pattern::Perm(_) => {
+                fs::symlink_metadata(path)
+                    .map(|meta| self.matches_meta(&meta))
+                    .unwrap_or(false)
+            },
+            Pattern::And(patterns) => patterns.iter().all(|pattern| pattern.matches(path)),
+            Pattern::Or(patterns) => patterns.iter().any(|pattern| pattern.matches(path)),
+            Pattern::Not(pattern) => !pattern.matches(path),
+        }
+    }
+
+    /// Checks the patterns that only need metadata. Anything else doesn't match.
+    pub fn matches_meta(&self, meta: &Metadata) -> bool {
+        match &self {
+            Pattern::Newer(field, time) => field.of(meta).map(|found| found > *time).unwrap_or(false),
+            Pattern::Older(field, time) => field.of(meta).map(|found| found < *time).unwrap_or(false),
+            Pattern::Uid(_) | Pattern::Gid(_) | Pattern::NoUser(_) | Pattern::NoGroup(_) | Pattern::Perm(_) => {
+                let (uid, gid, mode) = match owner_ids(meta) {
+                    Some(ids) => ids,
+                    None => return false,
+                };
+
+                match &self {
+                    Pattern::Uid(want) => uid == *want,
+                    Pattern::Gid(want) => gid == *want,
+                    Pattern::NoUser(known) => !known.contains(&uid),
+                    Pattern::NoGroup(known) => !known.contains(&gid),
+                    Pattern::Perm(mask) => mask.matches_mode(mode),
+                    _ => false,
+                }
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether this pattern is a filter on metadata, rather than something to match
+    /// the name (or contents) against.
+    pub fn is_metadata_filter(&self) -> bool {
+        matches!(
+            self,
+            Pattern::Newer(..) | Pattern::Older(..)
+                | Pattern::Uid(_) | Pattern::Gid(_)
+                | Pattern::NoUser(_) | Pattern::NoGroup(_)
+                | Pattern::Perm(_)
+        )
+    }
+
+    /// Whether this is built out of other patterns with And, Or, or Not.
+    pub fn is_combinator(&self) -> bool {
+        matches!(self, Pattern::And(_) | Pattern::Or(_) | Pattern::Not(_))
+    }
+
+    pub fn cost(&self) -> PatternCost {
+        match &self {
+            Pattern::Contains(_) => PatternCost::Content,
+            Pattern::And(patterns) | Pattern::Or(patterns) => {
+                patterns.iter()
+                    .map(|pattern| pattern.cost())
+                    .max()
+                    .unwrap_or(PatternCost::Name)
+            },
+            Pattern::Not(pattern) => pattern.cost(),
+            _ if self.is_metadata_filter() => PatternCost::Metadata,
+            _ => PatternCost::Name,
         }
     }
+
+    /// Like `matches`, but content searches also record the lines that matched on the
+    /// FileInfo, so the file doesn't need to be read twice.
+    pub fn matches_file(&self, file_info: &mut FileInfo) -> bool {
+        match &self {
+            Pattern::Contains(search) => {
+                if !file_info.meta.is_file() {
+                    return false;
+                }
+
+                match search.search_file(&file_info.path) {
+                    Some(lines) => {
+                        file_info.content_matches = Some(lines);
+                        true
+                    },
+                    None => false,
+                }
+            },
+            Pattern::Fuzzy(fuzzy, thresh) => {
+                let score = fuzzy_score_path(&file_info.path, fuzzy);
+
+                if score > *thresh {
+                    file_info.score = Some(score);
+                }
+
+                score > *thresh
+            },
+            _ if self.is_metadata_filter() => self.matches_meta(&file_info.meta),
+            Pattern::And(patterns) => patterns.iter().all(|pattern| pattern.matches_file(file_info)),
+            Pattern::Or(patterns) => patterns.iter().any(|pattern| pattern.matches_file(file_info)),
+            Pattern::Not(pattern) => {
+                // Anything a negated pattern finds (content matches, fuzzy scores) isn't
+                // really a match, so put back whatever was there before
+                let (content_matches, score) = (file_info.content_matches.take(), file_info.score);
+                let matched = pattern.matches_file(file_info);
+                file_info.content_matches = content_matches;
+                file_info.score = score;
+
+                !matched
+            },
+            _ => self.matches(&file_info.path),
+        }
+    }
+}
+
+// Stable, so patterns that cost the same keep the order they were given in
+fn sorted_by_cost(mut patterns: Vec<Pattern>) -> Vec<Pattern> {
+    patterns.sort_by_key(|pattern| pattern.cost());
+    patterns
+}
+
+impl From<Regex> for ContentSearch {
+    fn from(regex: Regex) -> ContentSearch {
+        ContentSearch::new(regex)
+    }
+}
+
+/// `!pattern` matches anything the pattern doesn't.
+impl Not for Pattern {
+    type Output = Pattern;
+
+    fn not(self) -> Pattern {
+        Pattern::Not(Box::new(self))
+    }
+}
+
+impl From<Glob> for Pattern {
+    fn from(glob: Glob) -> Pattern {
+        Pattern::Glob(glob)
+    }
 }
 
 impl From<FileType> for Pattern {
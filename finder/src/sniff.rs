@@ -0,0 +1,89 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use mime_guess::mime::Mime;
+
+
+// Enough to see every signature below, and to judge whether a file is text
+static SNIFF_LEN: usize = 512;
+
+
+/// How to work out what type a file is when matching against `FileType`s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TypeDetection {
+    // Guess from the extension. Doesn't touch the file at all.
+    #[default]
+    Extension,
+    // Read the start of the file and look for a known signature
+    Sniff,
+    // Match if either of the two does, only reading the file if the extension didn't match
+    Either,
+}
+
+
+/// Reads the start of a file and identifies it from its magic bytes. Files without a known
+/// signature that look like UTF-8 are `text/plain`. Returns None for anything else, or if
+/// the file couldn't be read.
+pub fn sniff_file(path: &Path) -> Option<Mime> {
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+
+    File::open(path).ok()?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut buf)
+        .ok()?;
+
+    sniff_bytes(&buf).and_then(|mime_str| mime_str.parse().ok())
+}
+
+/// Identifies the type of some content from its first few bytes.
+pub fn sniff_bytes(buf: &[u8]) -> Option<&'static str> {
+    let mime_str = match buf {
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => "image/png",
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', b'7', b'a', ..] | [b'G', b'I', b'F', b'8', b'9', b'a', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => sniff_iso_bmff(buf),
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => sniff_matroska(buf),
+        [b'%', b'P', b'D', b'F', b'-', ..] => "application/pdf",
+        [b'P', b'K', 0x03, 0x04, ..] | [b'P', b'K', 0x05, 0x06, ..] | [b'P', b'K', 0x07, 0x08, ..] => "application/zip",
+        [0x1F, 0x8B, ..] => "application/gzip",
+        [0x7F, b'E', b'L', b'F', ..] => "application/x-executable",
+        _ if is_text(buf) => "text/plain",
+        _ => return None,
+    };
+
+    Some(mime_str)
+}
+
+// MP4, QuickTime, HEIF and friends all share the ISO base media format, where the major
+// brand right after "ftyp" says which one it is.
+fn sniff_iso_bmff(buf: &[u8]) -> &'static str {
+    match buf.get(8..12) {
+        Some(b"heic") | Some(b"heix") | Some(b"mif1") | Some(b"msf1") => "image/heif",
+        Some(b"avif") | Some(b"avis") => "image/avif",
+        Some(b"qt  ") => "video/quicktime",
+        Some(b"M4A ") => "audio/mp4",
+        _ => "video/mp4",
+    }
+}
+
+// WebM is Matroska with its own doctype, which is close to the start of the header
+fn sniff_matroska(buf: &[u8]) -> &'static str {
+    match buf.windows(4).any(|window| window == b"webm") {
+        true => "video/webm",
+        false => "video/x-matroska",
+    }
+}
+
+fn is_text(buf: &[u8]) -> bool {
+    if buf.is_empty() || buf.contains(&0) {
+        return false;
+    }
+
+    match std::str::from_utf8(buf) {
+        Ok(_) => true,
+        // The read could have stopped partway through a multi-byte character
+        Err(err) => err.error_len().is_none() && buf.len() - err.valid_up_to() < 4,
+    }
+}
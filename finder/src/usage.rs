@@ -0,0 +1,238 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::walk::{DirJob, DirVisitor, FileInfo};
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeMode {
+    // The length of each file, like `du --apparent-size`
+    Apparent,
+    // The space actually allocated on disk, which is what `du` shows by default
+    Blocks,
+}
+
+impl SizeMode {
+    #[cfg(unix)]
+    pub fn of(&self, meta: &Metadata) -> u64 {
+        match self {
+            SizeMode::Apparent => meta.len(),
+            // st_blocks is always in 512 byte units, whatever the filesystem's block size
+            SizeMode::Blocks => meta.blocks() * 512,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn of(&self, meta: &Metadata) -> u64 {
+        meta.len()
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsageTotals {
+    pub size: u64,
+    // Every file and directory counted, including the directory itself
+    pub inodes: u64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, size: u64) {
+        self.size += size;
+        self.inodes += 1;
+    }
+
+    fn merge(&mut self, other: UsageTotals) {
+        self.size += other.size;
+        self.inodes += other.inodes;
+    }
+}
+
+
+/// The total size of everything below a directory.
+#[derive(Debug, Clone)]
+pub struct DirUsage {
+    pub path: PathBuf,
+    // The root is at 0
+    pub depth: usize,
+    pub totals: UsageTotals,
+}
+
+
+/// Adds up sizes for every directory under a root, like `du`. Hard links are only counted
+/// the first time they're seen.
+#[derive(Debug, Clone)]
+pub struct DiskUsage {
+    pub size_mode: SizeMode,
+    // Deeper directories still count towards their parents, they just aren't reported
+    pub max_depth: Option<usize>,
+}
+
+impl Default for DiskUsage {
+    fn default() -> Self {
+        Self {size_mode: SizeMode::Blocks, max_depth: None}
+    }
+}
+
+impl DiskUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_size_mode(mut self, size_mode: SizeMode) -> Self {
+        self.size_mode = size_mode;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Totals up the entries found under `root`. Entries can come from a search that's still
+    /// going, though all the adding up happens on the calling thread (`Finder::disk_usage`
+    /// spreads it across the pool instead). Directories are returned in path order, so each
+    /// one comes before anything inside it.
+    pub fn measure<I>(&self, root: &Path, entries: I) -> Vec<DirUsage>
+    where
+        I: IntoIterator<Item = FileInfo>
+    {
+        let counter = self.counter();
+
+        let partials = entries.into_iter()
+            .filter_map(|file_info| {
+                let dir = file_info.path.parent()?.to_path_buf();
+                let depth = file_info.depth.saturating_sub(1);
+
+                counter.partial(dir, depth, vec![file_info])
+            });
+
+        self.total(root, partials)
+    }
+
+    /// Visits each directory of a walk, adding up what's directly inside it.
+    pub(crate) fn counter(&self) -> UsageCounter {
+        UsageCounter {size_mode: self.size_mode, seen_links: Mutex::new(HashSet::new())}
+    }
+
+    /// Adds each directory's partial to every directory above it, and returns the totals.
+    pub(crate) fn total<I>(&self, root: &Path, partials: I) -> Vec<DirUsage>
+    where
+        I: IntoIterator<Item = DirPartial>
+    {
+        // Keyed on depth first, so the last entry is always one of the deepest directories left
+        let mut dirs: BTreeMap<(usize, PathBuf), UsageTotals> = BTreeMap::new();
+
+        // The root's own entry, since the walk only hands back what's inside it
+        let root_size = fs::metadata(root).map(|meta| self.size_mode.of(&meta)).unwrap_or(0);
+        dirs.entry((0, root.to_path_buf())).or_default().add(root_size);
+
+        for partial in partials {
+            for (sub_dir, size) in partial.sub_dirs {
+                dirs.entry((partial.depth + 1, sub_dir)).or_default().add(size);
+            }
+
+            dirs.entry((partial.depth, partial.path)).or_default().merge(partial.files);
+        }
+
+        let mut usage = Vec::with_capacity(dirs.len());
+
+        // Everything below a directory has been added to it by the time it comes off the end
+        while let Some(((depth, path), totals)) = dirs.pop_last() {
+            if let (Some(parent), true) = (path.parent(), depth > 0) {
+                dirs.entry((depth - 1, parent.to_path_buf())).or_default().merge(totals);
+            }
+
+            if self.max_depth.map(|max_depth| depth <= max_depth).unwrap_or(true) {
+                usage.push(DirUsage {path, depth, totals});
+            }
+        }
+
+        usage.sort_by(|a, b| a.path.cmp(&b.path));
+        usage
+    }
+}
+
+
+/// What one directory adds to the totals by itself: the files directly inside it, and the
+/// size of each subdirectory's own entry, without anything inside them.
+#[derive(Debug, Clone)]
+pub(crate) struct DirPartial {
+    path: PathBuf,
+    depth: usize,
+    files: UsageTotals,
+    sub_dirs: Vec<(PathBuf, u64)>,
+}
+
+
+/// Boils each directory of a walk down to a `DirPartial` on the worker that read it.
+pub(crate) struct UsageCounter {
+    size_mode: SizeMode,
+    // Shared by every worker, since links to the same file can be in different directories
+    seen_links: Mutex<HashSet<(u64, u64)>>,
+}
+
+impl UsageCounter {
+    // None if nothing in the directory counts towards the totals
+    fn partial(&self, path: PathBuf, depth: usize, entries: Vec<FileInfo>) -> Option<DirPartial> {
+        let mut partial = DirPartial {path, depth, files: UsageTotals::default(), sub_dirs: vec![]};
+
+        for file_info in entries {
+            if file_info.broken_link {
+                continue;
+            }
+
+            if let Some(link_id) = hard_link_id(&file_info.meta) {
+                if !self.seen_links.lock().expect("seen links lock poisoned").insert(link_id) {
+                    continue;
+                }
+            }
+
+            let size = self.size_mode.of(&file_info.meta);
+
+            // A directory counts towards itself, as well as everything above it
+            match file_info.meta.is_dir() {
+                true => partial.sub_dirs.push((file_info.path, size)),
+                false => partial.files.add(size),
+            }
+        }
+
+        match partial.files.inodes == 0 && partial.sub_dirs.is_empty() {
+            true => None,
+            false => Some(partial),
+        }
+    }
+}
+
+impl DirVisitor for UsageCounter {
+    type Output = DirPartial;
+    type Entered = ();
+
+    fn enter(&self, _dir: &DirJob) -> Option<()> {
+        Some(())
+    }
+
+    fn visit(&self, dir: &DirJob, _entered: (), matches: Vec<FileInfo>) -> Vec<DirPartial> {
+        self.partial(dir.path.clone(), dir.depth, matches).into_iter().collect()
+    }
+}
+
+// Files with more than one link show up once per link, but only take up space once. Directories
+// can't be hard linked, their link count is just their number of subdirectories.
+#[cfg(unix)]
+fn hard_link_id(meta: &Metadata) -> Option<(u64, u64)> {
+    match !meta.is_dir() && meta.nlink() > 1 {
+        true => Some((meta.dev(), meta.ino())),
+        false => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn hard_link_id(_meta: &Metadata) -> Option<(u64, u64)> {
+    None
+}
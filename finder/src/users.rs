@@ -0,0 +1,56 @@
+use std::collections::HashSet;
+use std::fs;
+
+
+static PASSWD_PATH: &str = "/etc/passwd";
+static GROUP_PATH: &str = "/etc/group";
+
+
+/// The (name, id) pairs from a passwd or group style file, where the name is the first field
+/// and the id is the third. Comments, blank lines, and anything malformed are skipped.
+fn read_id_file(path: &str) -> Vec<(String, u32)> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return vec![],
+    };
+
+    contents.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?.trim().parse::<u32>().ok()?;
+
+            Some((name.to_string(), id))
+        })
+        .collect()
+}
+
+/// Turns a user or group name into its id. A plain number is taken as the id itself, the same
+/// as `find -user 1000`.
+fn resolve(path: &str, name: &str) -> Option<u32> {
+    let name = name.trim();
+
+    read_id_file(path).into_iter()
+        .find(|(found, _)| found == name)
+        .map(|(_, id)| id)
+        .or_else(|| name.parse::<u32>().ok())
+}
+
+pub fn uid_for_user(user: &str) -> Option<u32> {
+    resolve(PASSWD_PATH, user)
+}
+
+pub fn gid_for_group(group: &str) -> Option<u32> {
+    resolve(GROUP_PATH, group)
+}
+
+/// Every uid with an entry in /etc/passwd.
+pub fn known_uids() -> HashSet<u32> {
+    read_id_file(PASSWD_PATH).into_iter().map(|(_, id)| id).collect()
+}
+
+/// Every gid with an entry in /etc/group.
+pub fn known_gids() -> HashSet<u32> {
+    read_id_file(GROUP_PATH).into_iter().map(|(_, id)| id).collect()
+}
@@ -1,10 +1,16 @@
 use std::fs;
 use std::fs::{DirEntry, Metadata, FileType};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use thread_pool::{ThreadPool, Job, JobResult};
+use thread_pool::{Task, TaskResult};
 
+use super::config::FinderConfig;
+use super::content::MatchedLine;
+use super::filesystem;
+use super::ignore::IgnoreStack;
+
+#[allow(unused_imports)]
 #[cfg(unix)]
 use std::os::unix::fs::{DirEntryExt, FileTypeExt, FileExt, MetadataExt};
 
@@ -13,6 +19,117 @@ use std::os::unix::fs::{DirEntryExt, FileTypeExt, FileExt, MetadataExt};
 pub struct FileInfo {
     pub path: PathBuf,
     pub meta: Metadata,
+    // How many directories below the root this was found. Entries in the root are at 1.
+    pub depth: usize,
+    // Filled in when a content search matched the file
+    pub content_matches: Option<Vec<MatchedLine>>,
+    // A symlink whose target doesn't exist. Only reported when following symlinks, and
+    // `meta` is for the link itself.
+    pub broken_link: bool,
+    // How well a fuzzy pattern matched, if one did
+    pub score: Option<isize>,
+}
+
+impl FileInfo {
+    pub fn new(path: PathBuf, meta: Metadata, depth: usize) -> Self {
+        Self {path, meta, depth, content_matches: None, broken_link: false, score: None}
+    }
+
+    pub fn broken_link(path: PathBuf, link_meta: Metadata, depth: usize) -> Self {
+        Self {broken_link: true, ..Self::new(path, link_meta, depth)}
+    }
+}
+
+
+#[cfg(unix)]
+fn dir_id(meta: &Metadata) -> Option<(u64, u64)> {
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_id(_meta: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[derive(Debug)]
+struct DirChainNode {
+    id: (u64, u64),
+    parent: Option<Arc<DirChainNode>>,
+}
+
+/// The (dev, inode) pair of every directory from the root down to a job's directory. When
+/// following symlinks, a link that points back at one of these would loop forever.
+#[derive(Debug, Clone, Default)]
+pub struct DirChain {
+    head: Option<Arc<DirChainNode>>,
+}
+
+impl DirChain {
+    pub fn push(&self, meta: &Metadata) -> Self {
+        match dir_id(meta) {
+            Some(id) => Self {head: Some(Arc::new(DirChainNode {id, parent: self.head.clone()}))},
+            None => self.clone(),
+        }
+    }
+
+    pub fn contains(&self, meta: &Metadata) -> bool {
+        let id = match dir_id(meta) {
+            Some(id) => id,
+            None => return false,
+        };
+
+        let mut node = self.head.as_ref();
+        while let Some(current) = node {
+            if current.id == id {
+                return true;
+            }
+
+            node = current.parent.as_ref();
+        }
+
+        false
+    }
+}
+
+
+/// A single directory for a worker to search, along with anything it inherited from the
+/// directories above it.
+#[derive(Debug, Clone)]
+pub struct DirJob {
+    pub path: PathBuf,
+    // The root is at 0
+    pub depth: usize,
+    pub ignores: IgnoreStack,
+    pub parents: DirChain,
+    // The device the directory is on, to spot subdirectories that are mount points
+    pub dev: u64,
+}
+
+impl DirJob {
+    pub fn new(path: PathBuf, ignores: IgnoreStack) -> Self {
+        let (parents, dev) = match fs::metadata(&path) {
+            Ok(meta) => (DirChain::default().push(&meta), filesystem::dev(&meta)),
+            Err(_) => (DirChain::default(), 0),
+        };
+
+        Self {path, depth: 0, ignores, parents, dev}
+    }
+
+    /// A job for the root of a search, which starts off with any ignore files above the root
+    /// if the config uses them.
+    pub fn for_root(root: PathBuf, config: &FinderConfig) -> Self {
+        let ignores = match config.use_ignore_files {
+            true => IgnoreStack::for_root(&root),
+            false => IgnoreStack::default(),
+        };
+
+        Self::new(root, ignores)
+    }
+
+    /// A job for one of this directory's subdirectories.
+    pub fn child(&self, path: PathBuf, ignores: IgnoreStack, meta: &Metadata) -> Self {
+        Self {path, depth: self.depth + 1, ignores, parents: self.parents.push(meta), dev: filesystem::dev(meta)}
+    }
 }
 
 pub enum HandleFile {
@@ -32,40 +149,144 @@ pub fn get_dir_entries(path: PathBuf) -> Option<impl Iterator<Item = DirEntry>>
 }
 
 
-pub fn recurse_find<F>(path: PathBuf, predicate: Arc<Box<F>>) -> impl FnOnce() -> Option<JobResult<Vec<FileInfo>>> + Sync + Send + 'static
+/// Follows an entry if it's a symlink and the config says to. A link whose target doesn't
+/// exist comes back as a broken link, with `meta` for the link itself.
+pub fn resolve_entry(config: &FinderConfig, path: PathBuf, meta: Metadata, depth: usize) -> FileInfo {
+    if config.follow_symlinks && meta.file_type().is_symlink() {
+        return match fs::metadata(&path) {
+            Ok(target_meta) => FileInfo::new(path, target_meta, depth),
+            Err(_) => FileInfo::broken_link(path, meta, depth),
+        };
+    }
+
+    FileInfo::new(path, meta, depth)
+}
+
+/// Whether an entry is one of the search's results. `ignores` are the ones in effect in the
+/// entry's directory.
+pub fn is_result(config: &FinderConfig, ignores: &IgnoreStack, file_info: &mut FileInfo) -> bool {
+    if config.use_ignore_files && ignores.is_ignored(&file_info.path, file_info.meta.is_dir()) {
+        return false;
+    }
+
+    let meta = &file_info.meta;
+
+    match file_info.broken_link {
+        true => config.report_broken_links && config.is_broken_link_match(file_info),
+        false => (meta.is_dir() || meta.is_file() || meta.file_type().is_symlink()) && config.is_match(file_info),
+    }
+}
+
+/// Whether the walk goes into a subdirectory found at `depth`, in a directory on `parent_dev`.
+/// Anything past the max depth, ignored, or on a filesystem we're staying out of isn't read
+/// at all.
+pub fn can_descend(config: &FinderConfig, ignores: &IgnoreStack, dir: &Path, meta: &Metadata, depth: usize, parent_dev: u64) -> bool {
+    !(config.use_ignore_files && ignores.is_ignored(dir, true))
+        && config.max_depth.map(|max_depth| depth < max_depth).unwrap_or(true)
+        && config.can_enter_dir(dir, meta, parent_dev)
+}
+
+
+/// Decides what a walk hands back for each directory it searches. A plain search passes the
+/// matches straight through, but a visitor can boil them down on the worker instead, so the
+/// calling thread only gets one result per directory.
+pub trait DirVisitor: Send + Sync + 'static {
+    type Output: Send + 'static;
+    // Whatever `enter` hands on to `visit` for the same directory
+    type Entered;
+
+    /// Called before a directory is read. Returning None skips it, and everything under it.
+    fn enter(&self, dir: &DirJob) -> Option<Self::Entered>;
+
+    /// Called with every match directly inside a directory, once it's been read. The job's
+    /// ignores include any ignore files in the directory itself.
+    fn visit(&self, dir: &DirJob, entered: Self::Entered, matches: Vec<FileInfo>) -> Vec<Self::Output>;
+}
+
+// Hands back every match as is
+struct FindMatches;
+
+impl DirVisitor for FindMatches {
+    type Output = FileInfo;
+    type Entered = ();
+
+    fn enter(&self, _dir: &DirJob) -> Option<()> {
+        Some(())
+    }
+
+    fn visit(&self, _dir: &DirJob, _entered: (), matches: Vec<FileInfo>) -> Vec<FileInfo> {
+        matches
+    }
+}
+
+
+/// Builds the task that searches a single directory. Matches are returned as results, and
+/// each subdirectory is handed back to the pool as a new task, so the walk spreads out
+/// across every worker.
+pub fn recurse_find(job: DirJob, config: Arc<FinderConfig>) -> impl FnOnce() -> Option<TaskResult<FileInfo>> + Send + 'static
+{
+    recurse_visit(job, config, Arc::new(FindMatches))
+}
+
+/// Like `recurse_find`, but each directory's matches go through the visitor, and whatever it
+/// returns are the results.
+pub fn recurse_visit<V>(job: DirJob, config: Arc<FinderConfig>, visitor: Arc<V>) -> impl FnOnce() -> Option<TaskResult<V::Output>> + Send + 'static
 where
-    F: FnOnce(FileInfo) -> bool + Send + Sync + 'static
+    V: DirVisitor
 {
-    let pred_clone = predicate.clone();
+    move || {
+        let entered = visitor.enter(&job)?;
+        let dir_entries: Vec<DirEntry> = get_dir_entries(job.path.clone())?.collect();
+
+        let ignores = match config.use_ignore_files {
+            true => job.ignores.enter_dir(&job.path, |name| {
+                dir_entries.iter().any(|entry| entry.file_name() == name)
+            }),
+            false => job.ignores.clone(),
+        };
 
-    return move || {
-        let dir_entries = get_dir_entries(path)?;
         let mut found_files = vec![];
+        let mut sub_dirs: Vec<Task<V::Output>> = vec![];
+
+        // Everything in this directory is one level deeper than the directory itself
+        let entry_depth = job.depth + 1;
 
         for entry in dir_entries {
+            let entry_path = entry.path();
+
+            // Pruned before it's even stat'd, so nothing under an excluded directory gets read
+            if config.is_skipped(&entry_path) {
+                continue;
+            }
+
             let meta = match entry.metadata() {
                 Ok(meta) => meta,
                 _ => continue
             };
 
-            let entry_path = entry.path();
-
-            if meta.is_dir() {
-                recurse_find(entry_path, pred_clone.clone());
-                continue;
-            }
+            let mut file_info = resolve_entry(&config, entry_path, meta, entry_depth);
 
-            if meta.is_file() {
-                let file_info = FileInfo {meta, path: entry_path};
+            if file_info.meta.is_dir() {
+                // A link back up to one of our own parents, so following it would never end
+                if config.follow_symlinks && job.parents.contains(&file_info.meta) {
+                    continue;
+                }
 
-                if pred_clone(file_info) {
-                    found_files.push(file_info);
+                if can_descend(&config, &ignores, &file_info.path, &file_info.meta, entry_depth, job.dev) {
+                    let sub_job = job.child(file_info.path.clone(), ignores.clone(), &file_info.meta);
+                    sub_dirs.push(Box::new(recurse_visit(sub_job, config.clone(), visitor.clone())));
                 }
             }
-        }
 
+            if is_result(&config, &ignores, &mut file_info) {
+                found_files.push(file_info);
+            }
+        }
 
-        Some(JobResult::Result(found_files))
-    };
+        let dir = DirJob {ignores, ..job};
 
+        TaskResult::from_results(visitor.visit(&dir, entered, found_files))
+            .add_jobs(sub_dirs)
+            .to_opt()
+    }
 }
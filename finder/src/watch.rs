@@ -0,0 +1,345 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::{CString, OsStr};
+use std::fs::{self, Metadata};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use thread_pool::ThreadPool;
+
+use super::config::FinderConfig;
+use super::ignore::IgnoreStack;
+use super::walk::{self, DirJob, DirVisitor, FileInfo};
+
+
+// Room for plenty of events per read, each of which is a header plus a name of up to NAME_MAX
+static EVENT_BUF_LEN: usize = 64 * 1024;
+
+static EVENT_HEADER_LEN: usize = std::mem::size_of::<libc::inotify_event>();
+
+static WATCH_MASK: u32 = libc::IN_CREATE | libc::IN_CLOSE_WRITE | libc::IN_ATTRIB | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO | libc::IN_DELETE | libc::IN_ONLYDIR;
+
+
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    // Created, moved in, or changed so that it matches now
+    Matched(Box<FileInfo>),
+    // Deleted, moved out, or changed so that it doesn't match anymore
+    Unmatched(PathBuf),
+}
+
+
+// What the walk that adds watches hands back
+enum Watched {
+    // A directory that's now being watched, with the ignore files in it
+    Dir(i32, DirJob),
+    Match(FileInfo),
+}
+
+// Watches each directory just before the walk reads it, so nothing created in the middle of
+// the walk gets missed
+struct WatchVisitor {
+    fd: RawFd,
+    // Shared by every worker, since two paths (through symlinks) can lead to the same directory
+    watched: Mutex<HashSet<i32>>,
+}
+
+impl DirVisitor for WatchVisitor {
+    type Output = Watched;
+    type Entered = i32;
+
+    fn enter(&self, dir: &DirJob) -> Option<i32> {
+        let wd = add_watch(self.fd, &dir.path).ok()?;
+
+        // Already being watched, most likely through a symlink that loops back up
+        match self.watched.lock().expect("watched lock poisoned").insert(wd) {
+            true => Some(wd),
+            false => None,
+        }
+    }
+
+    fn visit(&self, dir: &DirJob, wd: i32, matches: Vec<FileInfo>) -> Vec<Watched> {
+        std::iter::once(Watched::Dir(wd, dir.clone()))
+            .chain(matches.into_iter().map(Watched::Match))
+            .collect()
+    }
+}
+
+
+/// Keeps a search running after it's done, using inotify to see files start and stop
+/// matching. Every directory the search goes through gets watched, and the set of watches
+/// follows directories as they're created, moved and removed.
+///
+/// New files aren't checked until whoever created them closes them, so anything that matches
+/// on size or contents sees the finished file, and readers never get one that's half written.
+pub struct Watcher {
+    fd: RawFd,
+    root: PathBuf,
+    config: Arc<FinderConfig>,
+    // Walks whatever needs watching, whether that's the whole tree or a new directory
+    pool: ThreadPool<Watched>,
+    dirs: HashMap<i32, DirJob>,
+    watches: HashMap<PathBuf, i32>,
+    matched: HashSet<PathBuf>,
+    // Created but not yet closed
+    pending: HashSet<PathBuf>,
+}
+
+impl Watcher {
+    pub fn new(root: PathBuf, config: FinderConfig) -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            fd,
+            root,
+            pool: ThreadPool::create_pool(config.pool_size),
+            config: Arc::new(config),
+            dirs: HashMap::new(),
+            watches: HashMap::new(),
+            matched: HashSet::new(),
+            pending: HashSet::new(),
+        })
+    }
+
+    /// Watches every directory under the root, and returns everything that matches to
+    /// begin with. Each directory is watched before it's read, so nothing created in the
+    /// middle of the search gets missed.
+    pub fn start(&mut self) -> Vec<WatchEvent> {
+        let mut events = vec![];
+        self.add_tree(DirJob::for_root(self.root.clone(), &self.config), &mut events);
+
+        events
+    }
+
+    /// Blocks until something changes, and returns every file that started or stopped
+    /// matching. Can be empty if nothing that changed made a difference.
+    pub fn wait(&mut self) -> io::Result<Vec<WatchEvent>> {
+        let mut buf = vec![0u8; EVENT_BUF_LEN];
+
+        let n_read = loop {
+            let n_read = unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+
+            if n_read >= 0 {
+                break n_read as usize;
+            }
+
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        };
+
+        let mut events = vec![];
+        let mut offset = 0;
+
+        while offset + EVENT_HEADER_LEN <= n_read {
+            let header: libc::inotify_event = unsafe {
+                std::ptr::read_unaligned(buf[offset..].as_ptr() as *const libc::inotify_event)
+            };
+
+            let name_start = offset + EVENT_HEADER_LEN;
+            let name_end = name_start + header.len as usize;
+
+            // The name is padded out with NULs
+            let name = buf[name_start..name_end.min(n_read)].split(|byte| *byte == 0).next().unwrap_or(&[]);
+
+            self.handle_event(header.wd, header.mask, OsStr::from_bytes(name), &mut events);
+
+            offset = name_end;
+        }
+
+        Ok(events)
+    }
+
+    fn handle_event(&mut self, wd: i32, mask: u32, name: &OsStr, events: &mut Vec<WatchEvent>) {
+        // Events were dropped, so the only way to catch up is to look at everything again
+        if mask & libc::IN_Q_OVERFLOW != 0 {
+            self.rescan(events);
+            return;
+        }
+
+        // The kernel dropped the watch, because the directory is gone
+        if mask & libc::IN_IGNORED != 0 {
+            if let Some(dir) = self.dirs.remove(&wd) {
+                self.watches.remove(&dir.path);
+            }
+            return;
+        }
+
+        let dir = match self.dirs.get(&wd) {
+            Some(dir) => dir.clone(),
+            None => return,
+        };
+
+        let path = dir.path.join(name);
+
+        if self.config.is_skipped(&path) {
+            return;
+        }
+
+        if mask & (libc::IN_DELETE | libc::IN_MOVED_FROM) != 0 {
+            self.pending.remove(&path);
+
+            if mask & libc::IN_ISDIR != 0 {
+                self.remove_tree(&path, events);
+            }
+
+            self.unmatch(&path, events);
+            return;
+        }
+
+        let file_info = match fs::symlink_metadata(&path) {
+            Ok(meta) => walk::resolve_entry(&self.config, path, meta, dir.depth + 1),
+            Err(_) => return,
+        };
+
+        if mask & libc::IN_CREATE != 0 && is_being_written(&file_info.meta) {
+            self.pending.insert(file_info.path);
+            return;
+        }
+
+        if mask & libc::IN_CLOSE_WRITE != 0 {
+            self.pending.remove(&file_info.path);
+        }
+        else if self.pending.contains(&file_info.path) {
+            return;
+        }
+
+        let new_dir = file_info.meta.is_dir() && mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0;
+
+        if new_dir && walk::can_descend(&self.config, &dir.ignores, &file_info.path, &file_info.meta, file_info.depth, dir.dev) {
+            self.add_tree(dir.child(file_info.path.clone(), dir.ignores.clone(), &file_info.meta), events);
+        }
+
+        self.check(file_info, &dir.ignores, events);
+    }
+
+    // Watches a directory and everything under it, and reports whatever matches in there. The
+    // walk runs across the pool, same as a search.
+    fn add_tree(&mut self, job: DirJob, events: &mut Vec<WatchEvent>) {
+        let visitor = WatchVisitor {fd: self.fd, watched: Mutex::new(self.dirs.keys().copied().collect())};
+
+        self.pool.spawn(walk::recurse_visit(job, self.config.clone(), Arc::new(visitor)))
+            .expect("Could not submit the directory");
+
+        while let Some(watched) = self.pool.recv_result() {
+            match watched {
+                Watched::Dir(wd, dir) => {
+                    self.watches.insert(dir.path.clone(), wd);
+                    self.dirs.insert(wd, dir);
+                },
+                Watched::Match(file_info) => {
+                    if self.matched.insert(file_info.path.clone()) {
+                        events.push(WatchEvent::Matched(Box::new(file_info)));
+                    }
+                },
+            }
+        }
+    }
+
+    // Stops watching a directory that's gone, and everything under it
+    fn remove_tree(&mut self, dir: &Path, events: &mut Vec<WatchEvent>) {
+        let gone: Vec<(PathBuf, i32)> = self.watches.iter()
+            .filter(|(path, _)| path.starts_with(dir))
+            .map(|(path, wd)| (path.clone(), *wd))
+            .collect();
+
+        for (path, wd) in gone {
+            self.watches.remove(&path);
+            self.dirs.remove(&wd);
+
+            // Fails if the kernel already dropped it, which is fine
+            unsafe { libc::inotify_rm_watch(self.fd, wd) };
+        }
+
+        let unmatched: Vec<PathBuf> = self.matched.iter()
+            .filter(|path| path.starts_with(dir) && path.as_path() != dir)
+            .cloned()
+            .collect();
+
+        for path in unmatched {
+            self.unmatch(&path, events);
+        }
+
+        self.pending.retain(|path| !path.starts_with(dir));
+    }
+
+    fn rescan(&mut self, events: &mut Vec<WatchEvent>) {
+        for wd in self.dirs.keys() {
+            unsafe { libc::inotify_rm_watch(self.fd, *wd) };
+        }
+
+        self.dirs.clear();
+        self.watches.clear();
+        self.pending.clear();
+
+        let old_matched = std::mem::take(&mut self.matched);
+
+        let mut found = vec![];
+        self.add_tree(DirJob::for_root(self.root.clone(), &self.config), &mut found);
+
+        events.extend(old_matched.iter()
+            .filter(|path| !self.matched.contains(*path))
+            .map(|path| WatchEvent::Unmatched(path.clone())));
+
+        events.extend(found.into_iter()
+            .filter(|event| matches!(event, WatchEvent::Matched(file_info) if !old_matched.contains(&file_info.path))));
+    }
+
+    // Checks a single entry against the config, and reports it if that changed whether it matches
+    fn check(&mut self, mut file_info: FileInfo, ignores: &IgnoreStack, events: &mut Vec<WatchEvent>) {
+        let is_match = walk::is_result(&self.config, ignores, &mut file_info);
+
+        match (is_match, self.matched.contains(&file_info.path)) {
+            (true, false) => {
+                self.matched.insert(file_info.path.clone());
+                events.push(WatchEvent::Matched(Box::new(file_info)));
+            },
+            (false, true) => self.unmatch(&file_info.path, events),
+            _ => (),
+        }
+    }
+
+    fn unmatch(&mut self, path: &Path, events: &mut Vec<WatchEvent>) {
+        if self.matched.remove(path) {
+            events.push(WatchEvent::Unmatched(path.to_path_buf()));
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        // Nothing's running between walks, so this just stops the workers
+        self.pool.join();
+
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+
+fn add_watch(fd: RawFd, dir: &Path) -> io::Result<i32> {
+    let c_path = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    let wd = unsafe { libc::inotify_add_watch(fd, c_path.as_ptr(), WATCH_MASK) };
+
+    match wd {
+        -1 => Err(io::Error::last_os_error()),
+        wd => Ok(wd),
+    }
+}
+
+// A regular file that was just created is still open for writing, unless it's a new hard
+// link to an existing file, in which case no close is coming.
+fn is_being_written(meta: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    meta.is_file() && meta.nlink() == 1
+}
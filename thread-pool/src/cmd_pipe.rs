@@ -1,6 +1,6 @@
 use std::sync::{
     Arc, Mutex, MutexGuard, TryLockError,
-    atomic::{AtomicUsize, Ordering},
+    atomic::{AtomicBool, AtomicUsize, Ordering},
     mpsc::{self, Receiver, Sender},
 };
 
@@ -16,41 +16,87 @@ pub enum TrySendError {
 }
 
 
-#[derive(Clone)]
 pub struct CommandSender<D>
 where
-    D: Copy + Send + 'static
+    D: Send + 'static
 {
     sender: Arc<Mutex<Sender<Command<D>>>>,
     queue_size: Arc<AtomicUsize>,
+    // Tasks that have been sent, but haven't finished running yet. Unlike queue_size, this
+    // is only decremented once a task (and any tasks it spawned) have been handed off.
+    in_flight: Arc<AtomicUsize>,
 }
 
-#[derive(Clone)]
 pub struct CommandReceiver<D>
 where
-    D: Copy + Send + 'static
+    D: Send + 'static
 {
     receiver: Arc<Mutex<Receiver<Command<D>>>>,
     queue_size: Arc<AtomicUsize>,
 }
 
-#[derive(Clone)]
 pub struct CommandPipe<D>
 where
-    D: Copy + Send + 'static
+    D: Send + 'static
 {
     pub sender: CommandSender<D>,
     pub receiver: CommandReceiver<D>,
 
     queue_size: Arc<AtomicUsize>,
+    in_flight: Arc<AtomicUsize>,
+    cancelled: Arc<AtomicBool>,
+}
+
+
+// Clone is implemented by hand, since deriving it would require D: Clone, even though
+// we only ever clone the Arcs.
+impl<D> Clone for CommandSender<D>
+where
+    D: Send + 'static
+{
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            queue_size: self.queue_size.clone(),
+            in_flight: self.in_flight.clone(),
+        }
+    }
+}
+
+impl<D> Clone for CommandReceiver<D>
+where
+    D: Send + 'static
+{
+    fn clone(&self) -> Self {
+        Self {receiver: self.receiver.clone(), queue_size: self.queue_size.clone()}
+    }
+}
+
+impl<D> Clone for CommandPipe<D>
+where
+    D: Send + 'static
+{
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            receiver: self.receiver.clone(),
+            queue_size: self.queue_size.clone(),
+            in_flight: self.in_flight.clone(),
+            cancelled: self.cancelled.clone(),
+        }
+    }
 }
 
 
 impl<D> CommandSender<D>
 where
-    D: Copy + Send + 'static{
-    fn new(sender: Arc<Mutex<Sender<Command<D>>>>, queue_size: Arc<AtomicUsize>) -> Self {
-        Self {queue_size, sender}
+    D: Send + 'static{
+    fn new(
+        sender: Arc<Mutex<Sender<Command<D>>>>,
+        queue_size: Arc<AtomicUsize>,
+        in_flight: Arc<AtomicUsize>,
+    ) -> Self {
+        Self {queue_size, sender, in_flight}
     }
 
     fn send_many_inner(
@@ -60,6 +106,10 @@ where
     ) -> Result<(), TrySendError> {
         let n_jobs = jobs.len();
 
+        // Count the jobs as in flight before sending them, so a worker can't finish one
+        // and make the pool look idle before we've caught up.
+        self.in_flight.fetch_add(n_jobs, Ordering::SeqCst);
+
         let mut jobs_sent = 0;
         for job in jobs {
             match locked_sender.send(Command::New(job)) {
@@ -69,6 +119,7 @@ where
         }
 
         self.queue_size.fetch_add(jobs_sent, Ordering::SeqCst);
+        self.in_flight.fetch_sub(n_jobs - jobs_sent, Ordering::SeqCst);
 
         if n_jobs == jobs_sent {
             Ok(())
@@ -85,9 +136,12 @@ where
     }
 
     pub fn send_single(&self, job: Task<D>) -> Result<(), TrySendError> {
-        aquire!(self.sender)
-            .send(Command::New(job))
-            .map_err(|_| TrySendError::SendError)?;
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+
+        if aquire!(self.sender).send(Command::New(job)).is_err() {
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            return Err(TrySendError::SendError);
+        }
 
         self.queue_size.fetch_add(1, Ordering::SeqCst);
 
@@ -106,10 +160,14 @@ where
     }
 
     pub fn try_send_single(&self, job: Task<D>) -> Result<(), TrySendError> {
-        try_aquire!(&self.sender)
-            .map_err(|_| TrySendError::WouldBlock)?
-            .send(Command::New(job))
-            .map_err(|_| TrySendError::SendError)?;
+        let locked = try_aquire!(&self.sender).map_err(|_| TrySendError::WouldBlock)?;
+
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+
+        if locked.send(Command::New(job)).is_err() {
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            return Err(TrySendError::SendError);
+        }
 
         self.queue_size.fetch_add(1, Ordering::SeqCst);
 
@@ -128,12 +186,18 @@ where
             Shape::Batch(jobs) => self.try_send_many(jobs).map_err(|err| err.into()),
         }
     }
+
+    /// Marks a previously sent task as finished. Workers call this after the task's results
+    /// and any follow up tasks have been sent.
+    pub fn task_done(&self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 
 impl<D> CommandReceiver<D>
 where
-    D: Copy + Send + 'static
+    D: Send + 'static
 {
     fn new(receiver: Arc<Mutex<Receiver<Command<D>>>>, queue_size: Arc<AtomicUsize>) -> Self {
         Self {receiver, queue_size}
@@ -162,7 +226,7 @@ where
 
 impl<D> CommandPipe<D>
 where
-    D: Copy + Send + 'static
+    D: Send + 'static
 {
     pub fn new(queue_size: Arc<AtomicUsize>) -> Self {
         let (sender, receiver) = mpsc::channel();
@@ -170,10 +234,14 @@ where
         let sender = Arc::new(Mutex::new(sender));
         let receiver = Arc::new(Mutex::new(receiver));
 
+        let in_flight = Arc::new(AtomicUsize::new(0));
+
         Self {
-            sender: CommandSender::new(sender, queue_size.clone()),
+            sender: CommandSender::new(sender, queue_size.clone(), in_flight.clone()),
             receiver: CommandReceiver::new(receiver, queue_size.clone()),
             queue_size,
+            in_flight,
+            cancelled: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -184,4 +252,17 @@ where
     pub fn jobs_in_queue_relaxed(&self) -> usize {
         self.queue_size.load(Ordering::Relaxed)
     }
+
+    pub fn jobs_in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Tells the workers to skip any tasks still in the queue instead of running them.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
 }
@@ -23,8 +23,8 @@ mod test {
         let mut thread_pool: ThreadPool<u64> = ThreadPool::create_pool(Some(10));
         let job_size: u64 = 10.min(thread_pool.worker_count() as u64);
 
-        fn sleep_fac(dur: u64) -> &'static fn() -> Option<TaskResult<u64>> {
-            &move || {
+        fn sleep_fac(dur: u64) -> impl FnOnce() -> Option<TaskResult<u64>> + Send + 'static {
+            move || {
                 std::thread::sleep(Duration::from_secs(dur));
                 Some(TaskResult::from_result(dur))
             }
@@ -32,11 +32,7 @@ mod test {
 
         let start = Instant::now();
         for i in 0..job_size {
-            thread_pool.spawn(move || {
-                sleep(Duration::from_secs(i));
-
-                Some(TaskResult::from_result(i))
-            }).ok().expect("Could not submit job");
+            thread_pool.spawn(sleep_fac(i)).ok().expect("Could not submit job");
         }
 
         let results = thread_pool.join();
@@ -52,6 +48,64 @@ mod test {
         assert_eq!(results, (0..job_size).collect::<Vec<u64>>());
     }
 
+    #[test]
+    fn recv_result_drains_spawned_tasks() {
+        let thread_pool: ThreadPool<u64> = ThreadPool::create_pool(Some(4));
+
+        thread_pool.spawn(|| {
+            let children: Vec<Task<u64>> = (1..4)
+                .map(|i| -> Task<u64> { Box::new(move || Some(TaskResult::from_result(i))) })
+                .collect();
+
+            Some(TaskResult::from_result(0).add_jobs(children))
+        }).ok().expect("Could not submit job");
+
+        let mut results = Vec::new();
+        while let Some(result) = thread_pool.recv_result() {
+            results.push(result);
+        }
+
+        results.sort();
+
+        assert_eq!(results, vec![0, 1, 2, 3], "Results from spawned tasks were lost");
+        assert!(thread_pool.is_idle(), "Pool still has tasks in flight");
+    }
+
+    #[test]
+    fn recv_result_survives_a_panicking_task() {
+        let thread_pool: ThreadPool<u64> = ThreadPool::create_pool(Some(2));
+
+        thread_pool.spawn(|| panic!("task failed")).ok().expect("Could not submit job");
+        thread_pool.spawn(|| Some(TaskResult::from_result(1))).ok().expect("Could not submit job");
+
+        let mut results = Vec::new();
+        while let Some(result) = thread_pool.recv_result() {
+            results.push(result);
+        }
+
+        assert_eq!(results, vec![1]);
+        assert!(thread_pool.is_idle(), "The panicked task was never marked done");
+    }
+
+    #[test]
+    fn results_apply_backpressure() {
+        let mut thread_pool: ThreadPool<u64> = ThreadPool::create_pool(Some(1));
+        let n_results: u64 = 20_000;
+
+        thread_pool.spawn(move || Some(TaskResult::from_results((0..n_results).collect())))
+            .ok().expect("Could not submit job");
+
+        // Nothing is reading, so the worker should be stuck on a full channel
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!thread_pool.is_idle(), "Worker sent every result without blocking");
+
+        let first = thread_pool.recv_result();
+        let results = thread_pool.join();
+
+        assert_eq!(first, Some(0));
+        assert_eq!(results.len() as u64, n_results - 1, "Results were lost while the worker was blocked");
+    }
+
     /*
     #[test]
     fn repeat_jobs() {
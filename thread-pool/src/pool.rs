@@ -1,15 +1,24 @@
 use std::sync::Arc;
-use std::sync::mpsc::{self, Receiver};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
 use super::cmd_pipe::{CommandPipe, TrySendError};
-use super::task::Task;
+use super::task::TaskResult;
 use super::worker::Worker;
 
 
+// How long to wait on the results channel before re-checking whether the pool has gone idle.
+const RESULT_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+// Results that can be waiting to be picked up before workers block on sending more. Keeps
+// memory flat when the consumer is slower than the workers.
+const RESULT_CHANNEL_BOUND: usize = 4096;
+
+
 pub struct ThreadPool<D>
 where
-    D: Copy + Send + 'static
+    D: Send + 'static
 {
     workers: Vec<Worker<D>>,
 
@@ -24,7 +33,7 @@ where
 
 impl<D> ThreadPool<D>
 where
-    D: Copy + Send + 'static
+    D: Send + 'static
 {
     pub fn create_pool(max_workers_opt: Option<usize>) -> Self {
         let max_workers = max_workers_opt
@@ -34,7 +43,7 @@ where
         let queue_size = Arc::new(AtomicUsize::new(0));
 
         let job_channel = CommandPipe::new(queue_size.clone());
-        let (res_sender, res_receiver) = mpsc::channel();
+        let (res_sender, res_receiver) = mpsc::sync_channel(RESULT_CHANNEL_BOUND);
 
         // Create and initialize the workers
         let mut workers: Vec<Worker<D>> = Vec::with_capacity(max_workers);
@@ -72,8 +81,11 @@ where
             .count()
     }
 
-    pub fn spawn(&self, f: Task<D>) -> Result<usize, TrySendError> {
-        self.job_channel.sender.send_single(f)?;
+    pub fn spawn<F>(&self, f: F) -> Result<usize, TrySendError>
+    where
+        F: FnOnce() -> Option<TaskResult<D>> + Send + 'static
+    {
+        self.job_channel.sender.send_single(Box::new(f))?;
 
         Ok(self.job_channel.jobs_in_queue())
     }
@@ -93,7 +105,50 @@ where
         self.queue_size.load(Ordering::SeqCst)
     }
 
+    /// Number of tasks that have been spawned, but haven't finished running. This includes
+    /// tasks still sitting in the queue, and those currently being run by a worker.
+    pub fn jobs_in_flight(&self) -> usize {
+        self.job_channel.jobs_in_flight()
+    }
+
+    /// True once every spawned task (and every task those spawned) has finished.
+    pub fn is_idle(&self) -> bool {
+        self.jobs_in_flight() == 0
+    }
+
+    /// Waits for the next result without holding onto it. Returns None once the pool is
+    /// idle and every result has been handed out, so this can be looped on to drain the
+    /// pool while tasks are still running.
+    pub fn recv_result(&self) -> Option<D> {
+        loop {
+            match self.results_reciever.recv_timeout(RESULT_POLL_INTERVAL) {
+                Ok(result) => return Some(result),
+                Err(RecvTimeoutError::Disconnected) => return None,
+                Err(RecvTimeoutError::Timeout) => {
+                    // Workers send their results before marking a task done, so once we're
+                    // idle anything left is already sitting in the channel.
+                    if self.is_idle() {
+                        return self.results_reciever.try_recv().ok();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Like `recv_result`, but doesn't wait. Returns None if no result is ready right now.
+    pub fn try_recv_result(&self) -> Option<D> {
+        self.results_reciever.try_recv().ok()
+    }
+
+    /// Waits for every task to finish, then stops the workers and returns every result
+    /// that wasn't already handed out.
     pub fn join(&mut self) -> Vec<D> {
+        // Workers block once the results channel is full, so it has to be drained before
+        // they can finish
+        while let Some(result) = self.recv_result() {
+            self.results.as_mut().expect("results vec already consumed").push(result);
+        }
+
         for worker in &self.workers {
             worker.signal_termination();
         }
@@ -110,11 +165,30 @@ where
 
         self.results.take().expect("already consumed results")
     }
+
+    /// Stops the pool early. Queued tasks are dropped without running, tasks that are
+    /// already running are allowed to finish, and any results are thrown away.
+    pub fn cancel(&mut self) {
+        self.job_channel.cancel();
+
+        // Running tasks might be blocked on a full results channel
+        while self.recv_result().is_some() {}
+
+        for worker in &self.workers {
+            worker.signal_termination();
+        }
+
+        for mut worker in self.workers.drain(..) {
+            worker.join().expect("Error joining worker");
+        }
+
+        self.results = None;
+    }
 }
 
 impl<D> Default for ThreadPool<D>
 where
-    D: Copy + Send + 'static,
+    D: Send + 'static,
 {
     fn default() -> Self {
         Self::new()
@@ -124,7 +198,7 @@ where
 /*
 impl<'a, D, F> IntoIterator for &'a mut ThreadPool<D, F>
 where
-    D: Copy + Send + 'static,
+    D: Send + 'static,
     F: FnOnce() -> Option<TaskResult<D, F>>,
     F: Clone + Send + 'static
 {
@@ -139,12 +213,12 @@ where
 
 impl<D> Iterator for ThreadPool<D>
 where
-    D: Copy + Send + 'static
+    D: Send + 'static
 {
     type Item = D;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.results_reciever.recv().ok()
+        self.recv_result()
     }
 }
 
@@ -11,7 +11,7 @@ impl<R, F: FnOnce() -> R> FnBox<R> for F {
 }
 */
 
-pub type Task<D> = fn() -> Option<TaskResult<D>>;
+pub type Task<D> = Box<dyn FnOnce() -> Option<TaskResult<D>> + Send + 'static>;
 
 
 #[derive(Clone, Debug)]
@@ -36,7 +36,7 @@ impl<D> From<Vec<D>> for Shape<D> {
 #[derive(Default)]
 pub struct TaskResult<D>
 where
-    D: Copy + Send + 'static
+    D: Send + 'static
 {
     results: Option<Vec<D>>,
     jobs: Option<Vec<Task<D>>>,
@@ -45,7 +45,7 @@ where
 
 impl<D> TaskResult<D>
 where
-    D: Copy + Send + 'static
+    D: Send + 'static
 {
     pub fn new() -> Self {
         Self {results: Some(vec![]), jobs: Some(vec![])}
@@ -121,11 +121,11 @@ where
 
     pub fn get(mut self) -> (Option<Shape<D>>, Option<Shape<Task<D>>>) {
         let results = {
-            let raw_results = self.results.take().expect("TaskResult already consumed");
+            let mut raw_results = self.results.take().expect("TaskResult already consumed");
 
             match raw_results.len() {
                 0 => None,
-                1 => Some(Shape::Single(raw_results[0])),
+                1 => Some(Shape::Single(raw_results.remove(0))),
                 _ => Some(Shape::Batch(raw_results))
             }
         };
@@ -161,7 +161,12 @@ where
     pub fn has_results(&self) -> bool {
         ! self.results.as_ref().expect("").is_empty()
     }
+}
 
+impl<D> TaskResult<D>
+where
+    D: Clone + Send + 'static
+{
     pub fn clone_results(&self) -> Option<Vec<D>> {
         self.results.clone()
     }
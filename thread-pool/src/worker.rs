@@ -1,19 +1,19 @@
+use std::panic::{self, AssertUnwindSafe};
 use std::thread::{self, JoinHandle};
 use std::sync::{
     Arc, Weak,
     atomic::{AtomicBool, Ordering},
-    mpsc::Sender,
+    mpsc::SyncSender,
 
 };
 
-use super::task::{Task, Shape};
+use super::task::{Task, TaskResult, Shape};
 use super::cmd_pipe::CommandPipe;
 
 
-#[derive(Clone)]
 pub enum Command<D>
 where
-    D: Copy + Send + 'static
+    D: Send + 'static
 {
     New(Task<D>),
     Terminate,
@@ -22,7 +22,7 @@ where
 
 pub struct Worker<D>
 where
-    D: Copy + Send + 'static
+    D: Send + 'static
 {
     // General id for the worker
     pub id: usize,
@@ -31,18 +31,18 @@ where
     handle: Option<JoinHandle<()>>,
 
     command_pipe: CommandPipe<D>,
-    results_sender: Sender<D>,
+    results_sender: SyncSender<D>,
 }
 
 
 impl<D> Worker<D>
 where
-    D: Copy + Send + 'static
+    D: Send + 'static
 {
     pub fn new(
         id: usize,
         command_pipe: CommandPipe<D>,
-        results_sender: Sender<D>,
+        results_sender: SyncSender<D>,
     ) -> Self {
 
         let cmd: CommandPipe<D> = command_pipe.clone();
@@ -60,8 +60,13 @@ where
                     }
                 };
 
+                if cmd.is_cancelled() {
+                    cmd.sender.task_done();
+                    continue;
+                }
+
                 busy.store(true, Ordering::SeqCst);
-                let task_result = new_task();
+                let task_result = run_task(new_task);
                 busy.store(false, Ordering::SeqCst);
 
                 if let Some(result) = task_result {
@@ -84,6 +89,8 @@ where
                         }
                     }
                 }
+
+                cmd.sender.task_done();
             }
         });
 
@@ -138,8 +145,13 @@ where
                         }
                     };
 
+                    if cmd.is_cancelled() {
+                        cmd.sender.task_done();
+                        continue;
+                    }
+
                     busy.store(true, Ordering::SeqCst);
-                    let job_result = new_job();
+                    let job_result = run_task(new_job);
                     busy.store(false, Ordering::SeqCst);
 
                     if let Some(result) = job_result {
@@ -163,6 +175,8 @@ where
                             }
                         }
                     }
+
+                    cmd.sender.task_done();
                 }
             });
 
@@ -173,3 +187,14 @@ where
         }
     }
 }
+
+
+// A task that panics is treated like one that returned nothing. Either way it still gets
+// marked done, otherwise the pool would never go idle, and the worker lives on to run the
+// next one.
+fn run_task<D>(task: Task<D>) -> Option<TaskResult<D>>
+where
+    D: Send + 'static
+{
+    panic::catch_unwind(AssertUnwindSafe(task)).unwrap_or(None)
+}